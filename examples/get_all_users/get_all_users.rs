@@ -1,55 +1,65 @@
 use clap::{App, Arg};
 use cute_fox::{
-    requests::api_manager::{ApiManager, API_TIMEOUT_MS, API_VERSION},
-    stages::users::UserInteraction,
+    cancel::CancelToken, jobs, Config, CuteExecutor, CuteFox, CuteTask, CuteValue, RobberError,
 };
 use rusqlite::Connection;
 
 const START: i32 = 0;
 const STOP: i32 = 652_860_000;
-const FIELDS: &str = "verified, sex, bdate, city, country, home_town, has_photo, photo_max_orig, domain, has_mobile, contacts, site, education, universities, schools, status, last_seen, followers_count, occupation, nickname, relatives, relation, personal, connections, activities, interests, music, movies, tv, books, games, about, quotes, timezone, screen_name, maiden_name, career, military";
+const JOB: &str = "get_all_users";
 
 #[tokio::main]
 async fn main() {
     let matches = App::new("get all users")
         .author("PatriotRossii <patriotrossii2019@mail.ru")
         .arg(
-            Arg::with_name("database_path")
-                .short("db")
-                .long("database_path")
-                .value_name("DATABASE_PATH")
-                .takes_value(true)
-                .required(true),
-        )
-        .arg(
-            Arg::with_name("access_token")
-                .long("access_token")
-                .value_name("ACCESS_TOKEN")
+            Arg::with_name("config")
+                .long("config")
+                .value_name("CONFIG")
                 .takes_value(true)
                 .required(true),
         )
         .get_matches();
 
-    let db_path = matches.value_of("database_path").unwrap();
-    let access_token = matches.value_of("access_token").unwrap();
+    let mut config =
+        Config::load(matches.value_of("config").unwrap()).expect("Failed to load configuration");
+    config.apply_env().expect("Failed to apply environment");
 
-    let api = ApiManager::new(access_token, API_VERSION);
+    // Ctrl-C lets the requests in flight finish and their chunks get stored.
+    let cancel = CancelToken::new();
+    let token = cancel.clone();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            token.cancel();
+        }
+    });
 
-    let mut connection = Connection::open(&db_path).expect("Failed to open database");
+    let api = CuteFox::from_config(&config).expect("Invalid configuration");
+    let fields = config.fields("default").expect("No default fields");
+    let db_path = config.database_path.expect("No database path");
 
-    for i in START..=(STOP - START) / 1000 {
-        let ids = ((i * 1000)..((i + 1) * 1000)).collect::<Vec<i32>>();
-        let users = api.get_users(&ids, FIELDS).await;
+    let mut connection = Connection::open(db_path).expect("Failed to open database");
+    cute_fox::migrations::migrate(&mut connection).expect("Failed to migrate database");
+    if jobs::find(&connection, JOB)
+        .expect("Failed to read jobs")
+        .is_none()
+    {
+        jobs::create(&mut connection, JOB, &fields, START..STOP).expect("Failed to create job");
+    }
 
-        if let Ok(users) = users {
-            let tx = connection.transaction().unwrap();
-            for user in users {
-                user.store(&tx, "objects").unwrap();
+    // Stored chunks are skipped, so an interrupted run continues where it stopped.
+    let task = CuteTask::Job {
+        name: JOB.to_string(),
+        task: None,
+    };
+    match api.execute_with(task, cancel).await {
+        Ok(CuteValue::Job(job)) => println!("Stored {} of {} chunks", job.done, job.total),
+        Err(RobberError::Interrupted(value)) => {
+            if let CuteValue::Job(job) = *value {
+                println!("Interrupted, stored {} of {} chunks", job.done, job.total);
             }
-            tx.commit().unwrap();
-            println!("Saved users from {} to {}", i * 100, (i + 1) * 100);
         }
-
-        tokio::time::sleep(tokio::time::Duration::from_millis(API_TIMEOUT_MS)).await;
+        Ok(_) => unreachable!(),
+        Err(e) => eprintln!("Job stopped: {}", e),
     }
 }
@@ -1,5 +1,6 @@
 use clap::{App, Arg};
-use cute_fox::{requests::api_manager::API_VERSION, CuteExecutor, CuteFox, CuteTask};
+use cute_fox::{cancel::CancelToken, requests::api_manager::API_VERSION, stages::fields::FieldSet, CuteExecutor, CuteFox, CuteTask, CuteValue, RobberError};
+use futures::StreamExt;
 
 pub fn is_integer(x: String) -> Result<(), String> {
     match x.parse::<i32>() {
@@ -52,16 +53,32 @@ async fn main() {
     let from: i32 = matches.value_of("lower_bound").unwrap().parse().unwrap();
     let to: i32 = matches.value_of("upper_bound").unwrap().parse().unwrap();
     
-    let fields: String = match matches.values_of("field") {
-        Some(e) => e.collect::<Vec<&str>>().join(","),
-        None => String::from("")
+    let fields: FieldSet = match matches.values_of("field") {
+        Some(e) => e.map(|x| x.parse().expect("Unknown field")).collect(),
+        None => FieldSet::new()
     };
     let tokens: Vec<String> = matches.values_of("access_token").unwrap().map(|x| x.to_string()).collect::<Vec<String>>();
 
-    let fox = CuteFox::new(&tokens, API_VERSION);
+    let cancel = CancelToken::new();
+    let token = cancel.clone();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            token.cancel();
+        }
+    });
+
+    let fox = CuteFox::new(&tokens, API_VERSION).expect("Failed to build API managers");
     let task = CuteTask::GetUsers {
         user_ids: (from..to).collect::<Vec<i32>>(),
         fields,
     };
-    fox.execute(task).await.unwrap();
+    let mut chunks = fox.execute_stream_with(task, cancel);
+    while let Some(value) = chunks.next().await {
+        match value {
+            Ok(CuteValue::Users(users)) => println!("Fetched {} users", users.len()),
+            Ok(_) => {}
+            Err(RobberError::Interrupted(_)) => println!("Interrupted"),
+            Err(e) => panic!("Failed to fetch users: {}", e),
+        }
+    }
 }
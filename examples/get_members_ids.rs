@@ -13,7 +13,7 @@ async fn main() {
 
     let group_id = group_id.parse().expect("Please, specify correct group id");
 
-    let api = ApiManager::new(access_token, API_VERSION);
+    let api = ApiManager::new(access_token, API_VERSION).expect("Failed to build API manager");
     let members = api.get_members_ids(group_id).await;
 
     println!("{:#?}", members.unwrap());
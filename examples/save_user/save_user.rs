@@ -1,26 +1,40 @@
 use cute_fox::{
-    requests::api_manager::{ApiManager, API_VERSION},
+    requests::{api_manager::ApiManager, rate_limiter::RateLimiter},
     stages::groups::GroupInteraction,
+    Config,
 };
 use rusqlite::Connection;
 
-const FIELDS: &str = "verified, sex, bdate, city, country, home_town, has_photo, photo_max_orig, domain, has_mobile, contacts, site, education, universities, schools, status, last_seen, followers_count, occupation, nickname, relatives, relation, personal, connections, activities, interests, music, movies, tv, books, games, about, quotes, timezone, screen_name, maiden_name, career, military";
-
 #[tokio::main]
 async fn main() {
     let mut args = std::env::args();
 
     let _ = args.next().unwrap();
-    let db_path = args.next().expect("Please, specify argument: DB_PATH");
-    let access_token = args.next().expect("Please, specify argument: ACCESS_TOKEN");
+    let config_path = args.next().expect("Please, specify argument: CONFIG");
     let group_id = args.next().expect("Please, specify argument: GROUP_ID");
 
     let group_id = group_id.parse().expect("Please, specify correct group id");
 
-    let api = ApiManager::new(access_token, API_VERSION);
-    let members = api.get_members(group_id, FIELDS).await;
+    let mut config = Config::load(config_path).expect("Failed to load configuration");
+    config.apply_env().expect("Failed to apply environment");
+    config.validate().expect("Invalid configuration");
+
+    let access_token = config.tokens().expect("No access token")[0].clone();
+    let fields = config.fields("default").expect("No default fields");
+    let db_path = config.database_path.expect("No database path");
+
+    let api = ApiManager::builder(access_token.as_str())
+        .version(config.api_version)
+        .rate_limiter(
+            RateLimiter::for_token(&access_token, config.requests_per_second)
+                .expect("Conflicting rate limit"),
+        )
+        .build()
+        .unwrap();
+    let members = api.get_members(group_id, &fields).await;
 
     let mut connection = Connection::open(&db_path).expect("Failed to open database");
+    cute_fox::migrations::migrate(&mut connection).expect("Failed to migrate database");
 
     let tx = connection.transaction().unwrap();
     for member in members.unwrap() {
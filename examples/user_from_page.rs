@@ -15,7 +15,7 @@ async fn main() {
 
     let fields = args.next().expect("Please, specify fields to collect");
 
-    let api = ApiManager::new(access_token, API_VERSION);
+    let api = ApiManager::new(access_token, API_VERSION).expect("Failed to build API manager");
     let user = api.get_user(user_id, &fields).await;
 
     println!("{:#?}", user.unwrap());
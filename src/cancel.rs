@@ -0,0 +1,58 @@
+use std::{fmt, sync::Arc};
+
+use tokio::sync::watch;
+
+/// Asks a running task to stop, shared by clones.
+///
+/// Given to `CuteExecutor::execute_with`, it stops only that task. Once cancelled, no new
+/// request is issued: the ones in flight finish and their users are still returned, saved
+/// and recorded in the job journal, then the task returns `RobberError::Interrupted` with
+/// what it has.
+#[derive(Clone)]
+pub struct CancelToken {
+    sender: Arc<watch::Sender<bool>>,
+    receiver: watch::Receiver<bool>,
+}
+
+impl CancelToken {
+    pub fn new() -> Self {
+        let (sender, receiver) = watch::channel(false);
+        Self {
+            sender: Arc::new(sender),
+            receiver,
+        }
+    }
+
+    pub fn cancel(&self) {
+        // Never fails, `self` keeps a receiver.
+        let _ = self.sender.send(true);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        *self.receiver.borrow()
+    }
+
+    /// Completes once `cancel` is called on any clone.
+    pub async fn cancelled(&self) {
+        let mut receiver = self.receiver.clone();
+        while !*receiver.borrow() {
+            if receiver.changed().await.is_err() {
+                futures::future::pending::<()>().await;
+            }
+        }
+    }
+}
+
+impl Default for CancelToken {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for CancelToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CancelToken")
+            .field("cancelled", &self.is_cancelled())
+            .finish()
+    }
+}
@@ -0,0 +1,249 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+use crate::{
+    requests::{api_manager::API_VERSION, rate_limiter::USER_REQUESTS_PER_SECOND},
+    stages::fields::{FieldSet, UserField},
+    RobberError,
+};
+
+/// Prefix of the environment variables read by `Config::apply_env`.
+pub const ENV_PREFIX: &str = "CUTE_FOX_";
+
+pub(crate) const DEFAULT_TRANSACTION_SIZE: usize = 1000;
+
+/// Settings shared by the CLI and the examples, read from a TOML or JSON file.
+///
+/// ```toml
+/// api_version = "5.130"
+/// token_files = ["tokens.txt"]
+/// requests_per_second = 3
+/// database_path = "users.db"
+/// transaction_size = 1000
+///
+/// [fields]
+/// default = ["sex", "bdate", "city"]
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(default)]
+    pub tokens: Vec<String>,
+    /// Files holding one token per line. Blank lines and lines starting with `#` are skipped.
+    #[serde(default)]
+    pub token_files: Vec<PathBuf>,
+    #[serde(default = "default_api_version")]
+    pub api_version: String,
+    /// Named lists of profile fields, in addition to the built-in `FieldSet::PRESETS`.
+    #[serde(default)]
+    pub fields: HashMap<String, Vec<String>>,
+    #[serde(default = "default_requests_per_second")]
+    pub requests_per_second: u32,
+    pub database_path: Option<PathBuf>,
+    #[serde(default = "default_transaction_size")]
+    pub transaction_size: usize,
+}
+
+fn default_api_version() -> String {
+    API_VERSION.to_string()
+}
+
+fn default_requests_per_second() -> u32 {
+    USER_REQUESTS_PER_SECOND
+}
+
+fn default_transaction_size() -> usize {
+    DEFAULT_TRANSACTION_SIZE
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            tokens: Vec::new(),
+            token_files: Vec::new(),
+            api_version: default_api_version(),
+            fields: HashMap::new(),
+            requests_per_second: default_requests_per_second(),
+            database_path: None,
+            transaction_size: default_transaction_size(),
+        }
+    }
+}
+
+fn config_error<K: Into<String>, M: ToString>(key: K, message: M) -> RobberError {
+    RobberError::ConfigError {
+        key: key.into(),
+        message: message.to_string(),
+    }
+}
+
+fn parse_env<T: std::str::FromStr>(key: &str, value: &str) -> Result<T, RobberError>
+where
+    T::Err: ToString,
+{
+    value.trim().parse().map_err(|e| config_error(key, e))
+}
+
+fn split_list(value: &str) -> impl Iterator<Item = &str> {
+    value.split(',').map(str::trim).filter(|e| !e.is_empty())
+}
+
+impl Config {
+    /// Reads `path` as JSON if it ends with `.json` and as TOML otherwise.
+    ///
+    /// Relative `token_files` and `database_path` are resolved against the directory of `path`.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, RobberError> {
+        let path = path.as_ref();
+        let data = std::fs::read_to_string(path).map_err(|e| {
+            config_error("config", format!("cannot read {}: {}", path.display(), e))
+        })?;
+
+        let mut config = match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => Self::from_json_str(&data)?,
+            _ => Self::from_toml_str(&data)?,
+        };
+
+        if let Some(dir) = path.parent() {
+            for file in &mut config.token_files {
+                if file.is_relative() {
+                    *file = dir.join(&*file);
+                }
+            }
+            if let Some(database_path) = &mut config.database_path {
+                if database_path.is_relative() {
+                    *database_path = dir.join(&*database_path);
+                }
+            }
+        }
+
+        Ok(config)
+    }
+
+    pub fn from_toml_str(data: &str) -> Result<Self, RobberError> {
+        toml::from_str(data).map_err(|e| config_error("config", e))
+    }
+
+    pub fn from_json_str(data: &str) -> Result<Self, RobberError> {
+        serde_json::from_str(data).map_err(|e| config_error("config", e))
+    }
+
+    /// Applies `CUTE_FOX_*` overrides from the environment of the process.
+    pub fn apply_env(&mut self) -> Result<(), RobberError> {
+        self.apply_vars(std::env::vars())
+    }
+
+    /// Applies `CUTE_FOX_*` overrides from `vars`, ignoring every other variable.
+    ///
+    /// `TOKENS` and `TOKEN_FILES` are comma-separated and replace the lists of the file.
+    /// Errors name the variable that could not be used.
+    pub fn apply_vars<I, K, V>(&mut self, vars: I) -> Result<(), RobberError>
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        for (key, value) in vars {
+            let (key, value) = (key.as_ref(), value.as_ref());
+            let name = match key.strip_prefix(ENV_PREFIX) {
+                Some(name) => name,
+                None => continue,
+            };
+
+            match name {
+                "TOKENS" => self.tokens = split_list(value).map(str::to_string).collect(),
+                "TOKEN_FILES" => self.token_files = split_list(value).map(PathBuf::from).collect(),
+                "API_VERSION" => self.api_version = value.trim().to_string(),
+                "REQUESTS_PER_SECOND" => self.requests_per_second = parse_env(key, value)?,
+                "DATABASE_PATH" => self.database_path = Some(PathBuf::from(value)),
+                "TRANSACTION_SIZE" => self.transaction_size = parse_env(key, value)?,
+                // Names the file itself, see `Config::load`.
+                "CONFIG" => {}
+                _ => return Err(config_error(key, "unknown setting")),
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the values that cannot be caught while parsing.
+    pub fn validate(&self) -> Result<(), RobberError> {
+        if self.api_version.trim().is_empty() {
+            return Err(config_error("api_version", "must not be empty"));
+        }
+        if self.requests_per_second == 0 {
+            return Err(config_error("requests_per_second", "must be positive"));
+        }
+        if self.transaction_size == 0 {
+            return Err(config_error("transaction_size", "must be positive"));
+        }
+        if let Some(i) = self.tokens.iter().position(|e| e.trim().is_empty()) {
+            return Err(config_error(format!("tokens[{}]", i), "must not be empty"));
+        }
+        for name in self.fields.keys() {
+            self.fields(name)?;
+        }
+        Ok(())
+    }
+
+    /// Tokens of `tokens` followed by the ones of `token_files`. At least one is required.
+    pub fn tokens(&self) -> Result<Vec<String>, RobberError> {
+        let mut tokens = self.tokens.clone();
+
+        for (i, path) in self.token_files.iter().enumerate() {
+            let data = std::fs::read_to_string(path).map_err(|e| {
+                config_error(
+                    format!("token_files[{}]", i),
+                    format!("cannot read {}: {}", path.display(), e),
+                )
+            })?;
+            tokens.extend(
+                data.lines()
+                    .map(str::trim)
+                    .filter(|e| !e.is_empty() && !e.starts_with('#'))
+                    .map(str::to_string),
+            );
+        }
+
+        if tokens.is_empty() {
+            return Err(config_error("tokens", "at least one token is required"));
+        }
+        Ok(tokens)
+    }
+
+    /// Fields of the preset `name`, looked up in `fields` and then in `FieldSet::PRESETS`.
+    pub fn fields(&self, name: &str) -> Result<FieldSet, RobberError> {
+        let fields = match self.fields.get(name) {
+            Some(e) => e,
+            None => {
+                return FieldSet::preset(name)
+                    .ok_or_else(|| config_error(format!("fields.{}", name), "no such preset"))
+            }
+        };
+
+        let mut set = FieldSet::new();
+        for (i, field) in fields.iter().enumerate() {
+            let key = format!("fields.{}[{}]", name, i);
+            if field.trim().is_empty() {
+                return Err(config_error(key, "must not be empty"));
+            }
+            let field = field.parse::<UserField>().map_err(|e| match e {
+                e @ RobberError::FieldError { .. } => config_error(key, e.to_string()),
+                e => e,
+            })?;
+            set.insert(field);
+        }
+        Ok(set)
+    }
+
+    /// Fields of the `default` preset of the configuration file, if it has one.
+    pub fn default_fields(&self) -> Result<Option<FieldSet>, RobberError> {
+        if self.fields.contains_key("default") {
+            self.fields("default").map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+}
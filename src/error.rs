@@ -0,0 +1,76 @@
+use std::fmt;
+
+use tokio::task::JoinError;
+
+use crate::{requests::errors::VkApiError, stages::users::StoreError, CuteValue};
+
+#[derive(Debug)]
+pub enum RobberError {
+    SerdeError(serde_json::Error),
+    ReqwestError(reqwest::Error),
+    JoinError(JoinError),
+    APIError(VkApiError),
+    SqliteError(rusqlite::Error),
+    StoreError(StoreError),
+    /// The database was migrated by a newer release than this one.
+    SchemaError {
+        found: u32,
+        supported: u32,
+    },
+    /// A configuration value is missing or invalid; `key` names it, e.g. `storage.transaction_size`.
+    ConfigError {
+        key: String,
+        message: String,
+    },
+    /// `field` is not a field `users.get` knows.
+    FieldError {
+        field: String,
+    },
+    /// The job `name` is missing, already exists, or cannot be run or saved.
+    JobError {
+        name: String,
+        message: String,
+    },
+    /// None of the access tokens can be used, because none was given or all were disabled.
+    TokenError {
+        message: String,
+    },
+    /// VK returned nothing for `user_id`, which does not exist.
+    UserNotFound {
+        user_id: i32,
+    },
+    /// The task was cancelled before it finished. Holds what it got until then, the users
+    /// fetched so far or the job with the chunks stored so far. Streams and pipelines
+    /// already handed their users over, so theirs holds no users.
+    Interrupted(Box<CuteValue>),
+}
+
+impl fmt::Display for RobberError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RobberError::SerdeError(e) => write!(f, "failed to parse response: {}", e),
+            RobberError::ReqwestError(e) => write!(f, "request failed: {}", e),
+            RobberError::JoinError(e) => write!(f, "task failed: {}", e),
+            RobberError::APIError(e) => write!(f, "VK API error: {}", e),
+            RobberError::SqliteError(e) => write!(f, "SQLite error: {}", e),
+            RobberError::StoreError(e) => e.fmt(f),
+            RobberError::SchemaError { found, supported } => write!(
+                f,
+                "database schema version {} is newer than the supported version {}",
+                found, supported
+            ),
+            RobberError::ConfigError { key, message } => {
+                write!(f, "invalid configuration `{}`: {}", key, message)
+            }
+            RobberError::FieldError { field } => write!(f, "unknown user field `{}`", field),
+            RobberError::JobError { name, message } => write!(f, "job `{}`: {}", name, message),
+            RobberError::TokenError { message } => write!(f, "no usable access token: {}", message),
+            RobberError::UserNotFound { user_id } => write!(f, "user {} does not exist", user_id),
+            RobberError::Interrupted(_) => write!(f, "interrupted before the task finished"),
+        }
+    }
+}
+
+/// `Display` already includes the message of the wrapped error, so no variant returns it
+/// again as its source and error chains print every message once.
+impl std::error::Error for RobberError {}
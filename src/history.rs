@@ -0,0 +1,174 @@
+use rusqlite::{params, Connection, OptionalExtension};
+use serde_json::{Map, Value};
+
+use crate::{
+    migrations, schema,
+    stages::{
+        fields::{FieldSet, UserField},
+        users::User,
+    },
+    RobberError,
+};
+
+/// Versions of user profiles, written by `User::store` once `enable` was called on a database.
+pub const HISTORY_TABLE: &str = "user_history";
+
+/// Key in `schema::SETTINGS_TABLE` present once history is enabled.
+const SETTING: &str = "history";
+
+/// Keys every user from `users.get` has, whatever the requested fields.
+const BASE_KEYS: &[&str] = &["id", "first_name", "last_name", "deactivated", "is_closed"];
+
+/// Fields that change on almost every fetch and would make a new version each time.
+const VOLATILE_FIELDS: &[&str] = &["counters", "followers_count", "last_seen"];
+
+/// Profile of a user as it was from `valid_from` until `valid_to`, `None` for the current one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Version {
+    pub user: User,
+    pub valid_from: i64,
+    pub valid_to: Option<i64>,
+}
+
+/// Turns on history for the database, migrating it first. Users stored from then on
+/// get a new version whenever their profile differs from the previous one.
+pub fn enable(conn: &mut Connection) -> Result<(), RobberError> {
+    migrations::migrate(conn)?;
+    set_enabled(conn)
+}
+
+pub(crate) fn set_enabled(conn: &Connection) -> Result<(), RobberError> {
+    conn.execute(
+        &format!(
+            "INSERT OR REPLACE INTO {} (key, value) VALUES (?, '1')",
+            schema::SETTINGS_TABLE
+        ),
+        params![SETTING],
+    )
+    .map(|_| ())
+    .map_err(RobberError::SqliteError)
+}
+
+pub fn is_enabled(conn: &Connection) -> Result<bool, RobberError> {
+    has_history(conn).map_err(RobberError::SqliteError)
+}
+
+pub(crate) fn has_history(conn: &Connection) -> Result<bool, rusqlite::Error> {
+    if !migrations::has_table(conn, schema::SETTINGS_TABLE)? {
+        return Ok(false);
+    }
+    conn.prepare_cached(&format!(
+        "SELECT 1 FROM {} WHERE key = ?",
+        schema::SETTINGS_TABLE
+    ))?
+    .query_row(params![SETTING], |_| Ok(()))
+    .optional()
+    .map(|e| e.is_some())
+}
+
+fn snapshot(user: &User) -> Result<Map<String, Value>, rusqlite::Error> {
+    let value = serde_json::to_value(user)
+        .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+    let mut fields = match value {
+        Value::Object(e) => e,
+        _ => Map::new(),
+    };
+    for field in VOLATILE_FIELDS {
+        fields.remove(*field);
+    }
+    Ok(fields)
+}
+
+/// Keys of the snapshot filled by a request for `fields`.
+fn fetched_keys(fields: &FieldSet) -> Vec<&'static str> {
+    let mut keys = BASE_KEYS.to_vec();
+    for field in fields.iter() {
+        match field {
+            UserField::Contacts => keys.extend(&["mobile_phone", "home_phone"]),
+            UserField::Connections => {
+                keys.extend(&["skype", "facebook", "twitter", "livejournal", "instagram"])
+            }
+            UserField::Relation => keys.extend(&["relation", "relation_partner"]),
+            field => keys.push(field.as_str()),
+        }
+    }
+    keys
+}
+
+/// Starts a new version of `user` at `at` unless the current one is the same.
+/// Returns whether a version was written.
+///
+/// When the fields `user` was fetched with are known, only those are compared and
+/// the others keep their values from the current version.
+pub(crate) fn record(conn: &Connection, user: &User, at: i64) -> Result<bool, rusqlite::Error> {
+    let mut fields = snapshot(user)?;
+
+    let current: Option<String> = conn
+        .prepare_cached(&format!(
+            "SELECT snapshot FROM {} WHERE user_id = ? AND valid_to IS NULL",
+            HISTORY_TABLE
+        ))?
+        .query_row(params![user.id()], |row| row.get(0))
+        .optional()?;
+    if let (Some(current), Some(fetched)) = (&current, user.fetched_fields()) {
+        if let Ok(Value::Object(mut merged)) = serde_json::from_str(current) {
+            for key in fetched_keys(fetched) {
+                match fields.remove(key) {
+                    Some(value) => merged.insert(key.to_string(), value),
+                    None => merged.remove(key),
+                };
+            }
+            fields = merged;
+        }
+    }
+    let snapshot = Value::Object(fields).to_string();
+    if current.as_ref() == Some(&snapshot) {
+        return Ok(false);
+    }
+
+    conn.prepare_cached(&format!(
+        "UPDATE {} SET valid_to = ? WHERE user_id = ? AND valid_to IS NULL",
+        HISTORY_TABLE
+    ))?
+    .execute(params![at, user.id()])?;
+    conn.prepare_cached(&format!(
+        "INSERT INTO {} (user_id, snapshot, valid_from) VALUES (?, ?, ?)",
+        HISTORY_TABLE
+    ))?
+    .execute(params![user.id(), snapshot, at])?;
+
+    Ok(true)
+}
+
+/// Every version of the user `user_id`, oldest first.
+pub fn versions(conn: &Connection, user_id: i64) -> Result<Vec<Version>, RobberError> {
+    let mut statement = conn
+        .prepare(&format!(
+            "SELECT snapshot, valid_from, valid_to FROM {} WHERE user_id = ? ORDER BY valid_from, rowid",
+            HISTORY_TABLE
+        ))
+        .map_err(RobberError::SqliteError)?;
+    let rows = statement
+        .query_map(params![user_id], |row| {
+            Ok((row.get::<_, String>(0)?, row.get(1)?, row.get(2)?))
+        })
+        .map_err(RobberError::SqliteError)?;
+
+    let mut versions = Vec::new();
+    for row in rows {
+        let (snapshot, valid_from, valid_to) = row.map_err(RobberError::SqliteError)?;
+        versions.push(Version {
+            user: serde_json::from_str(&snapshot).map_err(RobberError::SerdeError)?,
+            valid_from,
+            valid_to,
+        });
+    }
+    Ok(versions)
+}
+
+/// Version of the user `user_id` that was current at `time`.
+pub fn as_of(conn: &Connection, user_id: i64, time: i64) -> Result<Option<Version>, RobberError> {
+    Ok(versions(conn, user_id)?
+        .into_iter()
+        .find(|e| e.valid_from <= time && e.valid_to.map(|to| time < to).unwrap_or(true)))
+}
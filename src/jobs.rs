@@ -0,0 +1,238 @@
+use itertools::Itertools;
+use rusqlite::{params, Connection, OptionalExtension};
+
+use crate::{
+    migrations::{self, unix_time},
+    schema::{JOBS_TABLE, JOB_CHUNKS_TABLE},
+    stages::fields::FieldSet,
+    RobberError,
+};
+
+/// User ids fetched and stored together, the most `users.get` accepts at once.
+pub const CHUNK_SIZE: usize = 1000;
+
+/// Journal of a `CuteTask::Job`, kept in the database it stores users to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Job {
+    pub name: String,
+    pub fields: FieldSet,
+    pub created_at: i64,
+    pub finished_at: Option<i64>,
+    /// Chunks stored so far.
+    pub done: usize,
+    pub total: usize,
+    /// Retries of the API calls made by every run so far.
+    pub retries: u64,
+}
+
+fn job_error(name: &str, message: &str) -> RobberError {
+    RobberError::JobError {
+        name: name.to_string(),
+        message: message.to_string(),
+    }
+}
+
+/// Writes ids as comma-separated runs, `1-1000` for every id from 1 to 1000 and
+/// `-5--3` for the ids from -5 to -3.
+fn encode(user_ids: &[i32]) -> String {
+    let mut runs: Vec<(i32, i32)> = Vec::new();
+    for &id in user_ids {
+        match runs.last_mut() {
+            Some((_, last)) if last.checked_add(1) == Some(id) => *last = id,
+            _ => runs.push((id, id)),
+        }
+    }
+
+    runs.into_iter()
+        .map(|(first, last)| {
+            if first == last {
+                first.to_string()
+            } else {
+                format!("{}-{}", first, last)
+            }
+        })
+        .join(",")
+}
+
+/// Runs written by `encode`, left as they are until the ids are needed.
+pub(crate) type Runs = Vec<(i32, i32)>;
+
+fn decode(user_ids: &str) -> Option<Runs> {
+    let mut result = Vec::new();
+    for run in user_ids.split(',').filter(|e| !e.is_empty()) {
+        // A leading `-` is the sign of the first id, not the separator.
+        match run
+            .get(1..)
+            .and_then(|e| e.find('-'))
+            .map(|i| run.split_at(i + 1))
+        {
+            Some((first, last)) => result.push((first.parse().ok()?, last[1..].parse().ok()?)),
+            None => {
+                let id = run.parse().ok()?;
+                result.push((id, id));
+            }
+        }
+    }
+    Some(result)
+}
+
+/// Every id of `runs`, in order.
+pub(crate) fn expand(runs: &[(i32, i32)]) -> Vec<i32> {
+    runs.iter()
+        .flat_map(|&(first, last)| first..=last)
+        .collect()
+}
+
+/// Creates the job `name` fetching `user_ids` with `fields`, split into chunks of `CHUNK_SIZE`.
+///
+/// Ids are consumed lazily, so a range of every VK user does not have to fit in memory.
+pub fn create<I>(
+    conn: &mut Connection,
+    name: &str,
+    fields: &FieldSet,
+    user_ids: I,
+) -> Result<Job, RobberError>
+where
+    I: IntoIterator<Item = i32>,
+{
+    migrations::migrate(conn)?;
+    if find(conn, name)?.is_some() {
+        return Err(job_error(name, "already exists"));
+    }
+
+    let tx = conn.transaction().map_err(RobberError::SqliteError)?;
+    tx.execute(
+        &format!(
+            "INSERT INTO {} (name, fields, created_at) VALUES (?, ?, ?)",
+            JOBS_TABLE
+        ),
+        params![name, fields.to_string(), unix_time()],
+    )
+    .map_err(RobberError::SqliteError)?;
+    {
+        let mut statement = tx
+            .prepare(&format!(
+                "INSERT INTO {} (job, chunk, user_ids) VALUES (?, ?, ?)",
+                JOB_CHUNKS_TABLE
+            ))
+            .map_err(RobberError::SqliteError)?;
+        for (i, chunk) in user_ids
+            .into_iter()
+            .chunks(CHUNK_SIZE)
+            .into_iter()
+            .enumerate()
+        {
+            let chunk: Vec<i32> = chunk.collect();
+            statement
+                .execute(params![name, i as i64, encode(&chunk)])
+                .map_err(RobberError::SqliteError)?;
+        }
+    }
+    tx.commit().map_err(RobberError::SqliteError)?;
+
+    find(conn, name)?.ok_or_else(|| job_error(name, "was not created"))
+}
+
+pub fn find(conn: &Connection, name: &str) -> Result<Option<Job>, RobberError> {
+    conn.query_row(
+        &format!(
+            "SELECT name, fields, created_at, finished_at,
+                (SELECT COUNT(*) FROM {1} WHERE job = name AND done_at IS NOT NULL),
+                (SELECT COUNT(*) FROM {1} WHERE job = name),
+                retries
+            FROM {0} WHERE name = ?",
+            JOBS_TABLE, JOB_CHUNKS_TABLE
+        ),
+        params![name],
+        |row| {
+            Ok(Job {
+                name: row.get(0)?,
+                fields: FieldSet::unchecked(&row.get::<_, String>(1)?),
+                created_at: row.get(2)?,
+                finished_at: row.get(3)?,
+                done: row.get::<_, i64>(4)? as usize,
+                total: row.get::<_, i64>(5)? as usize,
+                retries: row.get::<_, i64>(6)? as u64,
+            })
+        },
+    )
+    .optional()
+    .map_err(RobberError::SqliteError)
+}
+
+/// Forgets the job `name`, so the next run under that name starts over.
+pub fn remove(conn: &Connection, name: &str) -> Result<(), RobberError> {
+    for (table, column) in &[(JOB_CHUNKS_TABLE, "job"), (JOBS_TABLE, "name")] {
+        conn.execute(
+            &format!("DELETE FROM {} WHERE {} = ?", table, column),
+            params![name],
+        )
+        .map_err(RobberError::SqliteError)?;
+    }
+    Ok(())
+}
+
+/// Chunks of the job not stored yet, in order. Their ids stay runs, so a job over
+/// every VK user fits in memory.
+pub(crate) fn pending(conn: &Connection, name: &str) -> Result<Vec<(i64, Runs)>, RobberError> {
+    let mut statement = conn
+        .prepare_cached(&format!(
+            "SELECT chunk, user_ids FROM {} WHERE job = ? AND done_at IS NULL ORDER BY chunk",
+            JOB_CHUNKS_TABLE
+        ))
+        .map_err(RobberError::SqliteError)?;
+    let rows = statement
+        .query_map(params![name], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+        })
+        .map_err(RobberError::SqliteError)?;
+
+    let mut chunks = Vec::new();
+    for row in rows {
+        let (chunk, user_ids) = row.map_err(RobberError::SqliteError)?;
+        let user_ids = decode(&user_ids)
+            .ok_or_else(|| job_error(name, &format!("chunk {} has invalid user ids", chunk)))?;
+        chunks.push((chunk, user_ids));
+    }
+    Ok(chunks)
+}
+
+/// Marks `chunks` as stored, and the job as finished once none are left. `retries` are
+/// added to those of the job.
+pub(crate) fn complete(
+    conn: &Connection,
+    name: &str,
+    chunks: &[i64],
+    retries: u64,
+) -> Result<(), RobberError> {
+    let now = unix_time();
+    for chunk in chunks {
+        conn.prepare_cached(&format!(
+            "UPDATE {} SET done_at = ? WHERE job = ? AND chunk = ?",
+            JOB_CHUNKS_TABLE
+        ))
+        .and_then(|mut e| e.execute(params![now, name, chunk]))
+        .map_err(RobberError::SqliteError)?;
+    }
+
+    conn.execute(
+        &format!(
+            "UPDATE {} SET finished_at = ? WHERE name = ? AND finished_at IS NULL
+                AND NOT EXISTS (SELECT 1 FROM {} WHERE job = name AND done_at IS NULL)",
+            JOBS_TABLE, JOB_CHUNKS_TABLE
+        ),
+        params![now, name],
+    )
+    .map_err(RobberError::SqliteError)?;
+    if retries > 0 {
+        conn.execute(
+            &format!(
+                "UPDATE {} SET retries = retries + ? WHERE name = ?",
+                JOBS_TABLE
+            ),
+            params![retries as i64, name],
+        )
+        .map_err(RobberError::SqliteError)?;
+    }
+    Ok(())
+}
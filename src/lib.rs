@@ -1,144 +1,629 @@
-#![feature(async_closure)]
-#![feature(exact_size_is_empty)]
-
 use async_trait::async_trait;
+use futures::{
+    stream::{self, BoxStream},
+    StreamExt, TryStreamExt,
+};
 use itertools::Itertools;
-use stages::{
-    groups::GroupInteraction,
-    users::{User, UserInteraction},
+use rusqlite::Connection;
+use stages::{fields::FieldSet, groups::GroupInteraction, users::User};
+use std::{
+    convert::TryFrom,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
 };
-use std::{collections::VecDeque, sync::Arc};
-use tokio::task::JoinError;
 
-use requests::api_manager::ApiManager;
+use cancel::CancelToken;
+pub use config::Config;
+use config::DEFAULT_TRANSACTION_SIZE;
+pub use error::RobberError;
+use requests::{
+    api_manager::{count_retries, ApiManager, ApiStats},
+    health::TokenHealth,
+    rate_limiter::RateLimiter,
+};
 
+pub mod cancel;
+pub mod config;
+pub mod error;
+pub mod history;
+pub mod jobs;
+pub mod migrations;
+mod pipeline;
 pub mod requests;
+mod scheduler;
+pub mod schema;
 pub mod stages;
 
-#[derive(Debug)]
-pub enum RobberError {
-    SerdeError(serde_json::Error),
-    ReqwestError(reqwest::Error),
-    JoinError(JoinError),
-    APIError,
-}
-
 #[derive(Debug, Clone)]
 pub enum CuteTask {
-    GetMembers { group_id: i32, fields: String },
-    GetUsers { user_ids: Vec<i32>, fields: String },
+    GetMembers {
+        group_id: i32,
+        fields: FieldSet,
+    },
+    GetUsers {
+        user_ids: Vec<i32>,
+        fields: FieldSet,
+    },
+    /// Refetches the users of the database of `CuteFox` fetched more than `older_than` ago
+    /// and stores them in place. Returns the refreshed users.
+    ///
+    /// Only the stored values of `fields` are replaced. The others keep what an earlier
+    /// fetch got, even though `fetched_at` moves on.
+    RefreshUsers {
+        older_than: Duration,
+        fields: FieldSet,
+    },
+    /// Runs the job `name` in the database of `CuteFox`, storing users chunk by chunk and
+    /// skipping the chunks an earlier run already stored. When there is no such job yet,
+    /// it is created from the users `task` would fetch. Returns the job.
+    Job {
+        name: String,
+        task: Option<Box<CuteTask>>,
+    },
 }
 
 #[derive(Debug)]
 pub enum CuteValue {
     Users(Vec<User>),
+    /// Progress of a `CuteTask::Job`, whose users are already stored.
+    Job(jobs::Job),
+}
+
+/// What `SqliteStorage::save_with` does when a user cannot be stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SaveMode {
+    /// Roll back the current transaction and return the error.
+    Abort,
+    /// Record the user in `store_errors` and go on with the rest of the chunk.
+    RecordErrors,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SaveReport {
+    pub saved: usize,
+    pub failed: usize,
+    /// Retries of the API calls that fetched the users, counted by `CuteFox::run_pipeline`.
+    pub retries: u64,
 }
 
-pub trait SqliteStorage {
+pub trait SqliteStorage: Sized {
+    /// Stores the value, first migrating the database to the current schema.
     fn save(
         self,
         conn: &mut rusqlite::Connection,
         transaction_size: usize,
-    ) -> Result<(), rusqlite::Error>;
+    ) -> Result<(), RobberError> {
+        self.save_with(conn, transaction_size, SaveMode::Abort)
+            .map(|_| ())
+    }
+
+    fn save_with(
+        self,
+        conn: &mut rusqlite::Connection,
+        transaction_size: usize,
+        mode: SaveMode,
+    ) -> Result<SaveReport, RobberError>;
+}
+
+/// Stores `user` inside a savepoint, so a failure leaves none of its rows behind.
+fn store_or_record(
+    transaction: &rusqlite::Transaction,
+    user: &User,
+    history: bool,
+) -> Result<bool, RobberError> {
+    transaction
+        .execute_batch("SAVEPOINT store_user")
+        .map_err(RobberError::SqliteError)?;
+    match user.store_with_history(transaction, "objects", history) {
+        Ok(()) => {
+            transaction
+                .execute_batch("RELEASE store_user")
+                .map_err(RobberError::SqliteError)?;
+            Ok(true)
+        }
+        Err(RobberError::StoreError(e)) => {
+            transaction
+                .execute_batch("ROLLBACK TO store_user; RELEASE store_user")
+                .map_err(RobberError::SqliteError)?;
+            transaction.execute(
+                &format!(
+                    "INSERT INTO {} (user_id, \"table\", message, payload, failed_at) VALUES (?, ?, ?, ?, ?)",
+                    schema::STORE_ERRORS_TABLE
+                ),
+                rusqlite::params![
+                    e.user_id,
+                    e.table,
+                    e.source.to_string(),
+                    // Only failed users are serialized, so storing stays a single pass.
+                    serde_json::to_string(user).ok(),
+                    migrations::unix_time()
+                ],
+            )
+            .map_err(RobberError::SqliteError)?;
+            Ok(false)
+        }
+        Err(e) => Err(e),
+    }
 }
 
 impl SqliteStorage for CuteValue {
-    fn save(
+    fn save_with(
         self,
         conn: &mut rusqlite::Connection,
         transaction_size: usize,
-    ) -> Result<(), rusqlite::Error> {
+        mode: SaveMode,
+    ) -> Result<SaveReport, RobberError> {
+        (&self).save_with(conn, transaction_size, mode)
+    }
+}
+
+/// Saves a value that is still needed afterwards, e.g. to return it.
+impl SqliteStorage for &CuteValue {
+    fn save_with(
+        self,
+        conn: &mut rusqlite::Connection,
+        transaction_size: usize,
+        mode: SaveMode,
+    ) -> Result<SaveReport, RobberError> {
+        migrations::migrate(conn)?;
         match self {
-            CuteValue::Users(e) => {
-                let chunks: Vec<Vec<User>> = e
-                    .into_iter()
-                    .chunks(transaction_size)
-                    .into_iter()
-                    .map(|chunk| chunk.collect())
-                    .collect();
-                for chunk in chunks {
-                    let transaction = conn.transaction()?;
-                    for user in chunk {
-                        user.store(&transaction, "objects")?;
-                    }
-                    transaction.commit()?;
-                }
+            CuteValue::Users(e) => store_users(conn, e, transaction_size, mode),
+            CuteValue::Job(job) => Err(RobberError::JobError {
+                name: job.name.clone(),
+                message: "cannot be saved, its users are stored while it runs".to_string(),
+            }),
+        }
+    }
+}
+
+/// Stores `users` in a database already migrated to the current schema.
+pub(crate) fn store_users(
+    conn: &mut rusqlite::Connection,
+    users: &[User],
+    transaction_size: usize,
+    mode: SaveMode,
+) -> Result<SaveReport, RobberError> {
+    let history = history::is_enabled(conn)?;
+
+    let mut report = SaveReport::default();
+    for chunk in users.chunks(transaction_size) {
+        let transaction = conn.transaction().map_err(RobberError::SqliteError)?;
+        let saved = store_all(&transaction, chunk, mode, history)?;
+        transaction.commit().map_err(RobberError::SqliteError)?;
+        report.saved += saved.saved;
+        report.failed += saved.failed;
+    }
+    Ok(report)
+}
+
+/// Stores `users` in `transaction`, leaving the commit to the caller.
+fn store_all(
+    transaction: &rusqlite::Transaction,
+    users: &[User],
+    mode: SaveMode,
+    history: bool,
+) -> Result<SaveReport, RobberError> {
+    let mut report = SaveReport::default();
+    for user in users {
+        let saved = match mode {
+            SaveMode::Abort => {
+                user.store_with_history(transaction, "objects", history)?;
+                true
             }
+            SaveMode::RecordErrors => store_or_record(transaction, user, history)?,
+        };
+        if saved {
+            report.saved += 1;
+        } else {
+            report.failed += 1;
         }
-        Ok(())
     }
+    Ok(report)
 }
 
 #[async_trait]
 pub trait CuteExecutor {
-    async fn execute(&self, task: CuteTask) -> Result<CuteValue, RobberError>;
+    /// Runs `task` to the end. Users are returned in the order of the ids they were fetched for.
+    async fn execute(&self, task: CuteTask) -> Result<CuteValue, RobberError> {
+        self.execute_with(task, CancelToken::new()).await
+    }
+
+    /// Same as `execute`, but stops once `cancel` is cancelled.
+    ///
+    /// Requests in flight still finish, then `RobberError::Interrupted` is returned with
+    /// the users fetched so far, or the job with the chunks it stored.
+    async fn execute_with(
+        &self,
+        task: CuteTask,
+        cancel: CancelToken,
+    ) -> Result<CuteValue, RobberError>;
+
+    /// Same as `execute`, but yields the users of every chunk as soon as it is fetched,
+    /// so they can be stored and dropped instead of piling up. Chunks come in the order
+    /// they finish.
+    fn execute_stream(&self, task: CuteTask) -> BoxStream<'_, Result<CuteValue, RobberError>> {
+        self.execute_stream_with(task, CancelToken::new())
+    }
+
+    /// Same as `execute_stream`, but stops once `cancel` is cancelled. A stream stopped
+    /// before its last chunk ends with `RobberError::Interrupted` holding no users, since
+    /// the ones fetched were yielded before.
+    fn execute_stream_with(
+        &self,
+        task: CuteTask,
+        cancel: CancelToken,
+    ) -> BoxStream<'_, Result<CuteValue, RobberError>>;
 }
 
 #[async_trait]
 impl CuteExecutor for CuteFox {
-    async fn execute(&self, task: CuteTask) -> Result<CuteValue, RobberError> {
+    async fn execute_with(
+        &self,
+        task: CuteTask,
+        cancel: CancelToken,
+    ) -> Result<CuteValue, RobberError> {
         match task {
-            CuteTask::GetMembers { group_id, fields } => {
-                let spy_manager = &self.managers[0];
-                let user_ids = spy_manager.get_members_ids(group_id).await?;
+            CuteTask::GetMembers { .. }
+            | CuteTask::GetUsers { .. }
+            | CuteTask::RefreshUsers { .. } => {
+                let mut chunks = Vec::new();
+                let mut interrupted = false;
+                // The users are all there is to return, retries are only in `ApiStats`.
+                let mut results = self.indexed_stream(task, cancel, Default::default());
+                while let Some(result) = results.next().await {
+                    match result {
+                        Ok(chunk) => chunks.push(chunk),
+                        Err(RobberError::Interrupted(_)) => interrupted = true,
+                        Err(e) => return Err(e),
+                    }
+                }
+                // Chunks finish in any order, users come back in the order they were asked for.
+                chunks.sort_unstable_by_key(|(i, _)| *i);
 
-                self.execute(CuteTask::GetUsers { user_ids, fields }).await
-            }
-            CuteTask::GetUsers { user_ids, fields } => {
                 let mut result = Vec::new();
-
-                let mut chunks: VecDeque<Vec<i32>> = user_ids
-                    .into_iter()
-                    .chunks(1000)
-                    .into_iter()
-                    .map(|chunk| chunk.collect())
-                    .collect();
-
-                let fields = Arc::new(fields);
-                let mut tasks = Vec::new();
-
-                'inner: while !chunks.is_empty() {
-                    for manager in self.managers.as_ref() {
-                        let chunk = match chunks.pop_front() {
-                            Some(e) => e,
-                            None => break 'inner,
-                        };
-
-                        let new_manager = manager.clone();
-                        let fields = fields.clone();
-
-                        tasks.push(tokio::spawn(async move {
-                            new_manager
-                                .get_users_unchecked(&chunk, fields.as_ref())
-                                .await
-                        }));
+                for (_, value) in chunks {
+                    if let CuteValue::Users(mut users) = value {
+                        result.append(&mut users);
+                    }
+                }
+                if interrupted {
+                    Err(RobberError::Interrupted(Box::new(CuteValue::Users(result))))
+                } else {
+                    Ok(CuteValue::Users(result))
+                }
+            }
+            CuteTask::Job { name, task } => {
+                let mut conn = self.open_database().await?;
+                // Recorded with every stored chunk, what is left once the run stops.
+                let retries = Arc::new(AtomicU64::new(0));
+                let job = match (jobs::find(&conn, &name)?, task) {
+                    (Some(job), _) => job,
+                    (None, Some(task)) => {
+                        let (plan, planned) = count_retries(self.plan(*task)).await;
+                        retries.fetch_add(planned, Ordering::Relaxed);
+                        let (user_ids, fields) = plan?;
+                        jobs::create(&mut conn, &name, &fields, user_ids)?
+                    }
+                    (None, None) => {
+                        return Err(RobberError::JobError {
+                            name,
+                            message: "does not exist".to_string(),
+                        })
                     }
-                    tokio::time::sleep(tokio::time::Duration::from_millis(400)).await;
+                };
+
+                // Every chunk is marked done in the transaction that stores its users, so
+                // a failed or cancelled run resumes from the first chunk it did not store.
+                let (done, chunks): (Vec<i64>, Vec<jobs::Runs>) =
+                    jobs::pending(&conn, &name)?.into_iter().unzip();
+                let chunks = chunks.into_iter().map(|e| jobs::expand(&e));
+                let history = history::is_enabled(&conn)?;
+
+                let mut results = self.chunks_stream(chunks, &job.fields, cancel, retries.clone());
+                while let Some(result) = results.next().await {
+                    let (i, users) = match result {
+                        Ok(e) => e,
+                        // The job is returned below as it was left.
+                        Err(RobberError::Interrupted(_)) => break,
+                        Err(e) => {
+                            jobs::complete(&conn, &name, &[], retries.swap(0, Ordering::Relaxed))?;
+                            return Err(e);
+                        }
+                    };
+                    let transaction = conn.transaction().map_err(RobberError::SqliteError)?;
+                    store_all(&transaction, &users, SaveMode::Abort, history)?;
+                    jobs::complete(
+                        &transaction,
+                        &name,
+                        &done[i..=i],
+                        retries.swap(0, Ordering::Relaxed),
+                    )?;
+                    transaction.commit().map_err(RobberError::SqliteError)?;
                 }
+                // Finishes a job whose chunks were all stored before.
+                jobs::complete(&conn, &name, &[], retries.swap(0, Ordering::Relaxed))?;
 
-                for task in tasks {
-                    result.extend(task.await.map_err(RobberError::JoinError)??.drain(..));
+                match jobs::find(&conn, &name)? {
+                    Some(job) if job.finished_at.is_none() => {
+                        Err(RobberError::Interrupted(Box::new(CuteValue::Job(job))))
+                    }
+                    Some(job) => Ok(CuteValue::Job(job)),
+                    None => Err(RobberError::JobError {
+                        name,
+                        message: "was removed while running".to_string(),
+                    }),
                 }
+            }
+        }
+    }
+
+    fn execute_stream_with(
+        &self,
+        task: CuteTask,
+        cancel: CancelToken,
+    ) -> BoxStream<'_, Result<CuteValue, RobberError>> {
+        self.indexed_stream(task, cancel, Default::default())
+            .map_ok(|(_, value)| value)
+            .boxed()
+    }
+}
 
-                Ok(CuteValue::Users(result))
+impl CuteFox {
+    /// Users of `task` chunk by chunk, in the order they finish, each with the position
+    /// of its chunk among the ids to fetch. Retries of the calls made for them are added
+    /// to `retries`, except for jobs, which keep their own count.
+    pub(crate) fn indexed_stream(
+        &self,
+        task: CuteTask,
+        cancel: CancelToken,
+        retries: Arc<AtomicU64>,
+    ) -> BoxStream<'_, Result<(usize, CuteValue), RobberError>> {
+        match task {
+            CuteTask::GetUsers { user_ids, fields } => {
+                self.users_stream(user_ids, fields, cancel, retries)
+            }
+            CuteTask::GetMembers { .. } => {
+                let planned = retries.clone();
+                stream::once(async move {
+                    let (plan, n) = count_retries(self.plan(task)).await;
+                    planned.fetch_add(n, Ordering::Relaxed);
+                    plan
+                })
+                .map_ok(move |(user_ids, fields)| {
+                    self.users_stream(user_ids, fields, cancel.clone(), retries.clone())
+                })
+                .try_flatten()
+                .boxed()
             }
+            // The database is opened once, chunks are stored on a blocking thread each.
+            CuteTask::RefreshUsers { older_than, fields } => stream::once(async move {
+                let conn = self.open_database().await?;
+                let user_ids = stale_user_ids(&conn, older_than)?;
+                Ok((Arc::new(Mutex::new(conn)), user_ids, fields))
+            })
+            .map_ok(move |(conn, user_ids, fields)| {
+                let transaction_size = self.transaction_size;
+                self.users_stream(user_ids, fields, cancel.clone(), retries.clone())
+                    .and_then(move |(i, value)| {
+                        let conn = conn.clone();
+                        async move {
+                            tokio::task::spawn_blocking(move || {
+                                let mut conn = conn.lock().unwrap_or_else(|e| e.into_inner());
+                                if let CuteValue::Users(users) = &value {
+                                    store_users(
+                                        &mut conn,
+                                        users,
+                                        transaction_size,
+                                        SaveMode::Abort,
+                                    )?;
+                                }
+                                Ok((i, value))
+                            })
+                            .await
+                            .map_err(RobberError::JoinError)?
+                        }
+                    })
+            })
+            .try_flatten()
+            .boxed(),
+            // A job stores its chunks itself and yields only once it is done.
+            CuteTask::Job { .. } => stream::once(self.execute_with(task, cancel))
+                .map_ok(|value| (0, value))
+                .boxed(),
+        }
+    }
+
+    /// Fetches `user_ids` in chunks through the scheduler, with `CHUNKS_PER_MANAGER`
+    /// workers for each manager.
+    fn users_stream(
+        &self,
+        user_ids: Vec<i32>,
+        fields: FieldSet,
+        cancel: CancelToken,
+        retries: Arc<AtomicU64>,
+    ) -> BoxStream<'static, Result<(usize, CuteValue), RobberError>> {
+        let chunks: Vec<Vec<i32>> = user_ids
+            .into_iter()
+            .chunks(jobs::CHUNK_SIZE)
+            .into_iter()
+            .map(|chunk| chunk.collect())
+            .collect();
+
+        self.chunks_stream(chunks, &fields, cancel, retries)
+            .map_ok(|(i, users)| (i, CuteValue::Users(users)))
+            .boxed()
+    }
+
+    /// Fetches `chunks` as they are, yielding each with its position in `chunks`.
+    fn chunks_stream<I>(
+        &self,
+        chunks: I,
+        fields: &FieldSet,
+        cancel: CancelToken,
+        retries: Arc<AtomicU64>,
+    ) -> BoxStream<'static, Result<(usize, Vec<User>), RobberError>>
+    where
+        I: IntoIterator<Item = Vec<i32>>,
+        I::IntoIter: Send + 'static,
+    {
+        if self.managers.is_empty() {
+            return stream::once(async {
+                Err(RobberError::TokenError {
+                    message: "at least one access token is required".to_string(),
+                })
+            })
+            .boxed();
+        }
+
+        scheduler::fetch_users(
+            self.managers.clone(),
+            chunks,
+            Arc::new(fields.clone()),
+            CHUNKS_PER_MANAGER,
+            cancel,
+            retries,
+        )
+    }
+
+    /// Users `task` would fetch and the fields to fetch them with.
+    async fn plan(&self, task: CuteTask) -> Result<(Vec<i32>, FieldSet), RobberError> {
+        match task {
+            CuteTask::GetMembers { group_id, fields } => {
+                let mut error = None;
+                for manager in self.managers.iter() {
+                    if manager.health() != TokenHealth::Healthy {
+                        continue;
+                    }
+                    match manager.get_members_ids(group_id).await {
+                        Ok(user_ids) => return Ok((user_ids, fields)),
+                        // Only the token is to blame, the next one may do.
+                        Err(e) if manager.health() != TokenHealth::Healthy => error = Some(e),
+                        Err(e) => return Err(e),
+                    }
+                }
+                Err(error.unwrap_or_else(|| RobberError::TokenError {
+                    message: "every access token is disabled or rate limited".to_string(),
+                }))
+            }
+            CuteTask::GetUsers { user_ids, fields } => Ok((user_ids, fields)),
+            CuteTask::RefreshUsers { older_than, fields } => {
+                let conn = self.open_database().await?;
+                Ok((stale_user_ids(&conn, older_than)?, fields))
+            }
+            CuteTask::Job { name, .. } => Err(RobberError::JobError {
+                name,
+                message: "cannot be run inside another job".to_string(),
+            }),
         }
     }
 }
 
+/// Ids of the stored users fetched more than `older_than` ago, as `users.get` takes them.
+fn stale_user_ids(conn: &Connection, older_than: Duration) -> Result<Vec<i32>, RobberError> {
+    Ok(User::stale_ids(conn, older_than)?
+        .into_iter()
+        .filter_map(|e| i32::try_from(e).ok())
+        .collect())
+}
+
+/// Chunks `CuteFox` fetches at once per manager, when its rate limiter has room for them.
+const CHUNKS_PER_MANAGER: usize = 4;
+
 pub struct CuteFox {
     managers: Arc<Vec<Arc<ApiManager>>>,
+    database: Option<PathBuf>,
+    transaction_size: usize,
 }
 
 impl CuteFox {
-    pub fn new(tokens: &[String], api_version: &str) -> Self {
-        let managers: Vec<Arc<ApiManager>> = tokens
+    pub fn new(tokens: &[String], api_version: &str) -> Result<Self, RobberError> {
+        let managers = tokens
             .iter()
-            .map(|e| Arc::new(ApiManager::new(e, api_version)))
-            .collect();
+            .map(|e| ApiManager::new(e, api_version).map(Arc::new))
+            .collect::<Result<Vec<_>, _>>()?;
 
+        Ok(Self::from_arcs(managers))
+    }
+
+    /// Uses managers configured with `ApiManager::builder`, one per token.
+    pub fn from_managers(managers: Vec<ApiManager>) -> Self {
+        Self::from_arcs(managers.into_iter().map(Arc::new).collect())
+    }
+
+    fn from_arcs(managers: Vec<Arc<ApiManager>>) -> Self {
         Self {
             managers: Arc::new(managers),
+            database: None,
+            transaction_size: DEFAULT_TRANSACTION_SIZE,
         }
     }
+
+    /// Database used by tasks that read what was stored before, like `CuteTask::RefreshUsers`.
+    pub fn with_database<P: Into<PathBuf>>(mut self, path: P, transaction_size: usize) -> Self {
+        self.database = Some(path.into());
+        self.transaction_size = transaction_size;
+        self
+    }
+
+    /// Opens and migrates the database given to `with_database` on a blocking thread.
+    async fn open_database(&self) -> Result<Connection, RobberError> {
+        let path = self
+            .database
+            .clone()
+            .ok_or_else(|| RobberError::ConfigError {
+                key: "database_path".to_string(),
+                message: "this task needs a database".to_string(),
+            })?;
+
+        tokio::task::spawn_blocking(move || {
+            let mut conn = Connection::open(path).map_err(RobberError::SqliteError)?;
+            migrations::migrate(&mut conn)?;
+            Ok(conn)
+        })
+        .await
+        .map_err(RobberError::JoinError)?
+    }
+
+    /// One manager per token of `config`, limited to `config.requests_per_second` each,
+    /// using `config.database_path` if set.
+    pub fn from_config(config: &Config) -> Result<Self, RobberError> {
+        config.validate()?;
+
+        let managers = config
+            .tokens()?
+            .iter()
+            .map(|token| {
+                ApiManager::builder(token.as_str())
+                    .version(config.api_version.as_str())
+                    .rate_limiter(RateLimiter::for_token(token, config.requests_per_second)?)
+                    .build()
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let fox = Self::from_managers(managers);
+        Ok(match &config.database_path {
+            Some(path) => fox.with_database(path, config.transaction_size),
+            None => fox,
+        })
+    }
+
+    /// Retries made across all tokens since this `CuteFox` was created.
+    pub fn retries(&self) -> u64 {
+        self.managers.iter().map(|e| e.stats().retries()).sum()
+    }
+
+    /// Stats of every token, in the order the managers were given.
+    pub fn stats(&self) -> impl Iterator<Item = &ApiStats> + '_ {
+        self.managers.iter().map(|e| e.stats())
+    }
+
+    /// Health of every token, in the order the managers were given. Tokens that are not
+    /// `TokenHealth::Healthy` get no chunks until they recover.
+    pub fn health(&self) -> impl Iterator<Item = TokenHealth> + '_ {
+        self.managers.iter().map(|e| e.health())
+    }
 }
@@ -1,3 +1,556 @@
-fn main() {
-    println!("Hello, world!");
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use cute_fox::{
+    cancel::CancelToken,
+    history,
+    jobs::Job,
+    migrations,
+    requests::{api_manager::ApiManager, health::TokenHealth, rate_limiter::RateLimiter},
+    stages::{
+        fields::FieldSet,
+        users::{User, UserInteraction},
+    },
+    Config, CuteExecutor, CuteFox, CuteTask, CuteValue, RobberError, SaveMode, SaveReport,
+    SqliteStorage,
+};
+use rusqlite::{types::ValueRef, Connection, NO_PARAMS};
+use std::time::Duration;
+
+const EXIT_API: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+const EXIT_STORAGE: i32 = 3;
+const EXIT_NOT_FOUND: i32 = 4;
+const EXIT_INTERRUPTED: i32 = 130;
+
+fn is_integer(x: String) -> Result<(), String> {
+    match x.parse::<i32>() {
+        Ok(_) => Ok(()),
+        Err(_) => Err(format!("expected an integer, found `{}`", x)),
+    }
+}
+
+fn app() -> App<'static, 'static> {
+    let config = Arg::with_name("config")
+        .long("config")
+        .value_name("CONFIG")
+        .env("CUTE_FOX_CONFIG")
+        .takes_value(true)
+        .help("TOML or JSON configuration file, see `cute_fox::Config`");
+    let access_token = Arg::with_name("access_token")
+        .long("access_token")
+        .value_name("ACCESS_TOKEN")
+        .takes_value(true)
+        .multiple(true)
+        .number_of_values(1)
+        .help("Access token to use, may be repeated to spread requests");
+    let field = Arg::with_name("field")
+        .long("field")
+        .value_name("FIELD")
+        .takes_value(true)
+        .multiple(true)
+        .number_of_values(1)
+        .use_delimiter(true)
+        .help("Profile field to collect, may be repeated or comma-separated");
+    let preset = Arg::with_name("preset")
+        .long("preset")
+        .value_name("PRESET")
+        .takes_value(true)
+        .conflicts_with("field")
+        .help("Field preset to collect: basic, education, contacts, all or one of the configuration file");
+    let database_path = Arg::with_name("database_path")
+        .long("database_path")
+        .value_name("DATABASE_PATH")
+        .takes_value(true)
+        .help("SQLite database to store results in instead of printing them");
+    let transaction_size = Arg::with_name("transaction_size")
+        .long("transaction_size")
+        .value_name("TRANSACTION_SIZE")
+        .takes_value(true)
+        .validator(is_integer)
+        .help("Users stored per SQLite transaction");
+    let job = Arg::with_name("job")
+        .long("job")
+        .value_name("JOB")
+        .takes_value(true)
+        .help("Runs the command as a resumable job of this name in the database, continuing it if it exists");
+    let format = Arg::with_name("format")
+        .long("format")
+        .value_name("FORMAT")
+        .takes_value(true)
+        .possible_values(&["json", "jsonl"])
+        .default_value("json")
+        .help("Output format for printed results");
+
+    App::new("cute_fox")
+        .version(env!("CARGO_PKG_VERSION"))
+        .author("PatriotRossii <patriotrossii2019@mail.ru>")
+        .about("Collects VK profiles into SQLite")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .args(&[
+            config.global(true),
+            access_token.global(true),
+            field.global(true),
+            preset.global(true),
+            database_path.global(true),
+            transaction_size.global(true),
+            job.global(true),
+            format.global(true),
+        ])
+        .subcommand(
+            SubCommand::with_name("user")
+                .about("Fetches a single user")
+                .arg(
+                    Arg::with_name("user_id")
+                        .value_name("USER_ID")
+                        .required(true)
+                        .validator(is_integer),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("members")
+                .about("Fetches every member of a group")
+                .arg(
+                    Arg::with_name("group_id")
+                        .value_name("GROUP_ID")
+                        .required(true)
+                        .validator(is_integer),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("users")
+                .about("Fetches every user with an id in [LOWER_BOUND, UPPER_BOUND)")
+                .arg(
+                    Arg::with_name("lower_bound")
+                        .value_name("LOWER_BOUND")
+                        .required(true)
+                        .validator(is_integer),
+                )
+                .arg(
+                    Arg::with_name("upper_bound")
+                        .value_name("UPPER_BOUND")
+                        .required(true)
+                        .validator(is_integer),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("refresh")
+                .about("Refetches the stored users fetched more than OLDER_THAN hours ago")
+                .arg(
+                    Arg::with_name("older_than")
+                        .long("older_than")
+                        .value_name("OLDER_THAN")
+                        .takes_value(true)
+                        .default_value("24")
+                        .validator(is_integer)
+                        .help("Age in hours after which a stored user is refetched"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("init-db")
+                .about("Creates or migrates the database given by --database_path")
+                .arg(
+                    Arg::with_name("history")
+                        .long("history")
+                        .help("Keeps a version of every user whose profile changes on refetch"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("export")
+                .about("Prints the users stored in the database given by --database_path"),
+        )
+}
+
+fn usage_error(key: &str, message: &str) -> RobberError {
+    RobberError::ConfigError {
+        key: key.to_string(),
+        message: message.to_string(),
+    }
+}
+
+/// Value of the required integer argument `key`.
+fn integer(matches: &ArgMatches, key: &str) -> Result<i32, RobberError> {
+    let value = matches
+        .value_of(key)
+        .ok_or_else(|| usage_error(key, "is required"))?;
+    value
+        .parse()
+        .map_err(|_| usage_error(key, &format!("expected an integer, found `{}`", value)))
+}
+
+fn exit_code(error: &RobberError) -> i32 {
+    match error {
+        RobberError::ConfigError { .. }
+        | RobberError::FieldError { .. }
+        | RobberError::JobError { .. } => EXIT_USAGE,
+        RobberError::SqliteError(_)
+        | RobberError::StoreError(_)
+        | RobberError::SchemaError { .. } => EXIT_STORAGE,
+        RobberError::UserNotFound { .. } => EXIT_NOT_FOUND,
+        RobberError::Interrupted(_) => EXIT_INTERRUPTED,
+        _ => EXIT_API,
+    }
+}
+
+/// Configuration file, then `CUTE_FOX_*` variables, then command line arguments.
+fn config(matches: &ArgMatches) -> Result<Config, RobberError> {
+    let mut config = match matches.value_of("config") {
+        Some(path) => Config::load(path)?,
+        None => Config::default(),
+    };
+    config.apply_env()?;
+
+    if let Some(tokens) = matches.values_of("access_token") {
+        config.tokens = tokens.map(str::to_string).collect();
+        config.token_files.clear();
+    }
+    if let Some(path) = matches.value_of("database_path") {
+        config.database_path = Some(path.into());
+    }
+    if let Some(size) = matches.value_of("transaction_size") {
+        config.transaction_size = size
+            .parse()
+            .map_err(|_| usage_error("transaction_size", "must be positive"))?;
+    }
+
+    config.validate()?;
+    Ok(config)
+}
+
+/// Fields given by `--field` or `--preset`, or else the `default` preset, if any.
+fn explicit_fields(matches: &ArgMatches, config: &Config) -> Result<Option<FieldSet>, RobberError> {
+    if let Some(fields) = matches.values_of("field") {
+        return fields.map(str::parse).collect::<Result<_, _>>().map(Some);
+    }
+    match matches.value_of("preset") {
+        Some(preset) => config.fields(preset).map(Some),
+        None => config.default_fields(),
+    }
+}
+
+fn fields(matches: &ArgMatches, config: &Config) -> Result<FieldSet, RobberError> {
+    explicit_fields(matches, config).map(Option::unwrap_or_default)
+}
+
+/// Wraps `task` into the job given by `--job`, if any.
+fn job(matches: &ArgMatches, config: &Config, task: CuteTask) -> Result<CuteTask, RobberError> {
+    match matches.value_of("job") {
+        Some(_) if config.database_path.is_none() => {
+            Err(usage_error("database_path", "jobs need a database"))
+        }
+        Some(name) => Ok(CuteTask::Job {
+            name: name.to_string(),
+            task: Some(Box::new(task)),
+        }),
+        None => Ok(task),
+    }
+}
+
+/// Per-token stats, then the tokens that were disabled and why.
+fn report_tokens(fox: &CuteFox) {
+    for (i, stats) in fox.stats().enumerate() {
+        eprintln!(
+            "Token {}: {} calls, {} retries, {} failed, {} requeued, {} users ({:.1} per second)",
+            i + 1,
+            stats.calls(),
+            stats.retries(),
+            stats.failures(),
+            stats.requeued(),
+            stats.users(),
+            stats.users_per_second()
+        );
+    }
+    for (i, health) in fox.health().enumerate() {
+        if health != TokenHealth::Healthy {
+            eprintln!("Token {} {}", i + 1, health);
+        }
+    }
+}
+
+/// Token cancelled by the first Ctrl-C, the second one quits right away.
+fn cancel_on_ctrl_c() -> CancelToken {
+    let cancel = CancelToken::new();
+    let token = cancel.clone();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_err() {
+            return;
+        }
+        eprintln!("Stopping after the requests in flight, press Ctrl-C again to quit now");
+        token.cancel();
+
+        if tokio::signal::ctrl_c().await.is_ok() {
+            std::process::exit(EXIT_INTERRUPTED);
+        }
+    });
+    cancel
+}
+
+/// Value of a task, along with whether it was interrupted before it finished.
+fn interrupted(result: Result<CuteValue, RobberError>) -> Result<(CuteValue, bool), RobberError> {
+    match result {
+        Ok(value) => Ok((value, false)),
+        Err(RobberError::Interrupted(value)) => Ok((*value, true)),
+        Err(e) => Err(e),
+    }
+}
+
+/// Tells how to resume a run stopped by Ctrl-C and exits, once what it fetched is stored.
+fn report_interrupted(interrupted: bool, resume: &str) {
+    if interrupted {
+        eprintln!("Interrupted, {}", resume);
+        std::process::exit(EXIT_INTERRUPTED);
+    }
+}
+
+fn report_job(job: &Job) -> Result<(), RobberError> {
+    eprintln!(
+        "Job {}: {} of {} chunks stored, {} retries{}",
+        job.name,
+        job.done,
+        job.total,
+        job.retries,
+        if job.finished_at.is_some() {
+            ", finished"
+        } else {
+            ""
+        }
+    );
+    Ok(())
+}
+
+fn open_database(config: &Config) -> Result<Option<Connection>, RobberError> {
+    match &config.database_path {
+        Some(path) => Connection::open(path)
+            .map(Some)
+            .map_err(RobberError::SqliteError),
+        None => Ok(None),
+    }
+}
+
+fn print_json(matches: &ArgMatches, values: &[serde_json::Value]) -> Result<(), RobberError> {
+    if matches.value_of("format") == Some("jsonl") {
+        for value in values {
+            println!("{}", value);
+        }
+    } else {
+        let output = serde_json::to_string_pretty(values).map_err(RobberError::SerdeError)?;
+        println!("{}", output);
+    }
+    Ok(())
+}
+
+fn report_saved(report: &SaveReport) {
+    eprintln!(
+        "Saved {} users, {} failed and were recorded in store_errors",
+        report.saved, report.failed
+    );
+    // Only the pipeline counts them, the per-token stats have the others.
+    if report.retries > 0 {
+        eprintln!("Fetching them took {} retries", report.retries);
+    }
+}
+
+fn output(matches: &ArgMatches, config: &Config, users: Vec<User>) -> Result<(), RobberError> {
+    match open_database(config)? {
+        Some(mut conn) => {
+            let report = CuteValue::Users(users).save_with(
+                &mut conn,
+                config.transaction_size,
+                SaveMode::RecordErrors,
+            )?;
+            report_saved(&report);
+            Ok(())
+        }
+        None => {
+            let values = users
+                .iter()
+                .map(serde_json::to_value)
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(RobberError::SerdeError)?;
+            print_json(matches, &values)
+        }
+    }
+}
+
+fn export(matches: &ArgMatches, config: &Config) -> Result<(), RobberError> {
+    let conn = open_database(config)?
+        .ok_or_else(|| usage_error("database_path", "export needs a database"))?;
+
+    let mut statement = conn
+        .prepare("SELECT * FROM objects ORDER BY id")
+        .map_err(RobberError::SqliteError)?;
+    let columns: Vec<String> = statement
+        .column_names()
+        .into_iter()
+        .map(str::to_string)
+        .collect();
+
+    let mut values = Vec::new();
+    let mut rows = statement
+        .query(NO_PARAMS)
+        .map_err(RobberError::SqliteError)?;
+    while let Some(row) = rows.next().map_err(RobberError::SqliteError)? {
+        let mut object = serde_json::Map::new();
+        for (i, column) in columns.iter().enumerate() {
+            let value = match row.get_raw(i) {
+                ValueRef::Null => serde_json::Value::Null,
+                ValueRef::Integer(e) => e.into(),
+                ValueRef::Real(e) => e.into(),
+                ValueRef::Text(e) => String::from_utf8_lossy(e).into(),
+                ValueRef::Blob(_) => continue,
+            };
+            object.insert(column.clone(), value);
+        }
+        values.push(serde_json::Value::Object(object));
+    }
+
+    print_json(matches, &values)
+}
+
+async fn run(matches: ArgMatches<'static>) -> Result<(), RobberError> {
+    let (command, sub_matches) = matches.subcommand();
+    let sub_matches = sub_matches.expect("subcommand is required");
+    let config = config(sub_matches)?;
+
+    match command {
+        "init-db" => {
+            let mut conn = open_database(&config)?
+                .ok_or_else(|| usage_error("database_path", "init-db needs a database"))?;
+            let from = migrations::migrate(&mut conn)?;
+            eprintln!(
+                "Database migrated from version {} to {}",
+                from,
+                migrations::latest_version()
+            );
+            if sub_matches.is_present("history") {
+                history::enable(&mut conn)?;
+                eprintln!("History of user profiles is enabled");
+            }
+            Ok(())
+        }
+        "export" => export(sub_matches, &config),
+        "user" => {
+            let token = config.tokens()?.remove(0);
+            let user_id = integer(sub_matches, "user_id")?;
+
+            let api = ApiManager::builder(token.as_str())
+                .version(config.api_version.as_str())
+                .rate_limiter(RateLimiter::for_token(&token, config.requests_per_second)?)
+                .build()?;
+            let user = api.get_user(user_id, fields(sub_matches, &config)?).await?;
+            output(sub_matches, &config, vec![user])
+        }
+        "members" | "users" => {
+            let fields = fields(sub_matches, &config)?;
+
+            let task = if command == "members" {
+                let group_id = integer(sub_matches, "group_id")?;
+                CuteTask::GetMembers { group_id, fields }
+            } else {
+                let from = integer(sub_matches, "lower_bound")?;
+                let to = integer(sub_matches, "upper_bound")?;
+                CuteTask::GetUsers {
+                    user_ids: (from..to).collect(),
+                    fields,
+                }
+            };
+
+            let cancel = cancel_on_ctrl_c();
+            let fox = CuteFox::from_config(&config)?;
+            let task = job(sub_matches, &config, task)?;
+            // Jobs store their chunks themselves, other tasks go through the writer pipeline.
+            if config.database_path.is_some() && !matches!(task, CuteTask::Job { .. }) {
+                let report = fox
+                    .run_pipeline_with(task, SaveMode::RecordErrors, cancel)
+                    .await;
+                // Tokens are reported even after a failure, they are often the reason for it.
+                report_tokens(&fox);
+                match report {
+                    Ok(report) => report_saved(&report),
+                    Err(RobberError::Interrupted(_)) => {
+                        report_interrupted(true, "use --job to make the run resumable")
+                    }
+                    Err(e) => return Err(e),
+                }
+                return Ok(());
+            }
+            let value = fox.execute_with(task, cancel).await;
+            report_tokens(&fox);
+            match interrupted(value)? {
+                (CuteValue::Users(users), interrupted) => {
+                    output(sub_matches, &config, users)?;
+                    report_interrupted(interrupted, "use --job to make the run resumable");
+                }
+                (CuteValue::Job(job), interrupted) => {
+                    report_job(&job)?;
+                    report_interrupted(interrupted, "run the same command again to resume the job");
+                }
+            }
+            Ok(())
+        }
+        "refresh" => {
+            if config.database_path.is_none() {
+                return Err(usage_error("database_path", "refresh needs a database"));
+            }
+            let hours: u64 = sub_matches
+                .value_of("older_than")
+                .ok_or_else(|| usage_error("older_than", "is required"))?
+                .parse()
+                .map_err(|_| usage_error("older_than", "must not be negative"))?;
+
+            let cancel = cancel_on_ctrl_c();
+            let fox = CuteFox::from_config(&config)?;
+            let task = CuteTask::RefreshUsers {
+                older_than: Duration::from_secs(hours * 3600),
+                // Without fields only the names and the account state would be refreshed.
+                fields: explicit_fields(sub_matches, &config)?.ok_or_else(|| {
+                    usage_error(
+                        "fields",
+                        "refresh needs --field, --preset or a default preset",
+                    )
+                })?,
+            };
+            let task = job(sub_matches, &config, task)?;
+            if let CuteTask::Job { .. } = task {
+                let value = fox.execute_with(task, cancel).await;
+                report_tokens(&fox);
+                let (value, interrupted) = interrupted(value)?;
+                if let CuteValue::Job(job) = value {
+                    report_job(&job)?;
+                }
+                report_interrupted(interrupted, "run the same command again to resume the job");
+                return Ok(());
+            }
+
+            let report = fox
+                .run_pipeline_with(task, SaveMode::RecordErrors, cancel)
+                .await;
+            report_tokens(&fox);
+            match report {
+                Ok(report) => report_saved(&report),
+                Err(RobberError::Interrupted(_)) => {
+                    report_interrupted(true, "run the same command again to refresh the rest")
+                }
+                Err(e) => return Err(e),
+            }
+            Ok(())
+        }
+        _ => unreachable!(),
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    // Clap exits with 1 on its own, which would read as an API failure.
+    let matches = match app().get_matches_safe() {
+        Ok(e) => e,
+        Err(e) if !e.use_stderr() => e.exit(),
+        Err(e) => {
+            eprintln!("{}", e.message);
+            std::process::exit(EXIT_USAGE);
+        }
+    };
+
+    if let Err(e) = run(matches).await {
+        eprintln!("error: {}", e);
+        std::process::exit(exit_code(&e));
+    }
 }
@@ -0,0 +1,213 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rusqlite::{params, Connection, OptionalExtension, Transaction, NO_PARAMS};
+
+use crate::{history, schema, stages::birth_date::BirthDate, RobberError};
+
+pub struct Migration {
+    pub version: u32,
+    pub description: &'static str,
+    apply: fn(&Transaction) -> Result<(), RobberError>,
+}
+
+/// Every step a database has to go through, in order.
+///
+/// Steps may run on databases created before `schema_version` existed, so they
+/// have to check what is already there instead of assuming the previous step's layout.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "create missing tables and indexes",
+        apply: |tx| schema::create(tx),
+    },
+    Migration {
+        version: 2,
+        description: "add objects.relation",
+        apply: |tx| add_column(tx, "objects", "relation", "INTEGER"),
+    },
+    Migration {
+        version: 3,
+        description: "add store_errors",
+        apply: |tx| schema::create(tx),
+    },
+    Migration {
+        version: 4,
+        description: "split objects.bdate into day, month and year",
+        apply: split_bdate,
+    },
+    Migration {
+        version: 5,
+        description: "add objects.fetched_at",
+        apply: |tx| add_column(tx, "objects", "fetched_at", "INTEGER"),
+    },
+    Migration {
+        version: 6,
+        description: "add jobs and job_chunks",
+        apply: |tx| schema::create(tx),
+    },
+    Migration {
+        version: HISTORY_VERSION,
+        description: "add settings and user_history",
+        apply: |tx| schema::create(tx),
+    },
+    Migration {
+        version: 8,
+        description: "add jobs.retries",
+        apply: |tx| add_column(tx, "jobs", "retries", "INTEGER NOT NULL DEFAULT 0"),
+    },
+];
+
+/// Version that made `user_history` part of the schema.
+const HISTORY_VERSION: u32 = 7;
+
+pub fn latest_version() -> u32 {
+    MIGRATIONS.last().map(|e| e.version).unwrap_or(0)
+}
+
+pub(crate) fn has_table(conn: &Connection, table: &str) -> Result<bool, rusqlite::Error> {
+    conn.query_row(
+        "SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?",
+        params![table],
+        |_| Ok(()),
+    )
+    .optional()
+    .map(|e| e.is_some())
+}
+
+fn has_column(conn: &Connection, table: &str, column: &str) -> Result<bool, rusqlite::Error> {
+    let mut statement = conn.prepare(&format!("PRAGMA table_info(\"{}\")", table))?;
+    let mut rows = statement.query(NO_PARAMS)?;
+    while let Some(row) = rows.next()? {
+        if row.get::<_, String>(1)? == column {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Adds `column` to `table` unless it is already there.
+pub(crate) fn add_column(
+    conn: &Connection,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> Result<(), RobberError> {
+    if !has_column(conn, table, column).map_err(RobberError::SqliteError)? {
+        conn.execute_batch(&format!(
+            "ALTER TABLE \"{}\" ADD COLUMN \"{}\" {}",
+            table, column, definition
+        ))
+        .map_err(RobberError::SqliteError)?;
+    }
+    Ok(())
+}
+
+/// Fills the new columns from the stored strings. Dates that do not parse are left empty.
+fn split_bdate(tx: &Transaction) -> Result<(), RobberError> {
+    for column in &["bdate_day", "bdate_month", "bdate_year"] {
+        add_column(tx, "objects", column, "INTEGER")?;
+    }
+
+    let mut statement = tx
+        .prepare("SELECT id, bdate FROM objects WHERE bdate IS NOT NULL")
+        .map_err(RobberError::SqliteError)?;
+    let rows = statement
+        .query_map(NO_PARAMS, |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+        })
+        .map_err(RobberError::SqliteError)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(RobberError::SqliteError)?;
+
+    for (id, bdate) in rows {
+        if let Ok(date) = bdate.parse::<BirthDate>() {
+            tx.execute(
+                "UPDATE objects SET bdate_day = ?, bdate_month = ?, bdate_year = ? WHERE id = ?",
+                params![date.day(), date.month(), date.year(), id],
+            )
+            .map_err(RobberError::SqliteError)?;
+        }
+    }
+    Ok(())
+}
+
+/// Version recorded in `schema_version`, or `None` for a database that has never been migrated.
+pub fn current_version(conn: &Connection) -> Result<Option<u32>, RobberError> {
+    if !has_table(conn, "schema_version").map_err(RobberError::SqliteError)? {
+        return Ok(None);
+    }
+    conn.query_row(
+        "SELECT MAX(version) FROM schema_version",
+        NO_PARAMS,
+        |row| row.get(0),
+    )
+    .map_err(RobberError::SqliteError)
+}
+
+pub(crate) fn unix_time() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|e| e.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+fn record(tx: &Transaction, version: u32, description: &str) -> Result<(), rusqlite::Error> {
+    tx.execute(
+        "INSERT INTO schema_version (version, description, applied_at) VALUES (?, ?, ?)",
+        params![version, description, unix_time()],
+    )?;
+    Ok(())
+}
+
+/// Brings the database up to `latest_version`, returning the version it started from.
+///
+/// An empty database gets the current schema directly. A database with tables but
+/// no `schema_version` is treated as version 0 and goes through every step. A database
+/// migrated by a newer release is refused with `RobberError::SchemaError`.
+pub fn migrate(conn: &mut Connection) -> Result<u32, RobberError> {
+    let tx = conn.transaction().map_err(RobberError::SqliteError)?;
+
+    let found = current_version(&tx)?;
+    if let Some(found) = found {
+        if found > latest_version() {
+            return Err(RobberError::SchemaError {
+                found,
+                supported: latest_version(),
+            });
+        }
+    }
+    let fresh = found.is_none() && !has_table(&tx, "objects").map_err(RobberError::SqliteError)?;
+
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS schema_version (
+            version INTEGER NOT NULL PRIMARY KEY,
+            description TEXT NOT NULL,
+            applied_at INTEGER NOT NULL
+        )",
+    )
+    .map_err(RobberError::SqliteError)?;
+
+    let from = found.unwrap_or(0);
+    if fresh {
+        schema::create(&tx)?;
+        record(&tx, latest_version(), "create schema").map_err(RobberError::SqliteError)?;
+    } else {
+        // Databases that got `user_history` from `history::enable` before it was a
+        // migration keep recording versions. Every step that runs `schema::create` adds
+        // the table, so whether it was there is read before any step runs.
+        let history_enabled = from < HISTORY_VERSION
+            && has_table(&tx, history::HISTORY_TABLE).map_err(RobberError::SqliteError)?;
+
+        for migration in MIGRATIONS.iter().filter(|e| e.version > from) {
+            (migration.apply)(&tx)?;
+            record(&tx, migration.version, migration.description)
+                .map_err(RobberError::SqliteError)?;
+        }
+        if history_enabled {
+            history::set_enabled(&tx)?;
+        }
+    }
+
+    tx.commit().map_err(RobberError::SqliteError)?;
+    Ok(from)
+}
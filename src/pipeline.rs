@@ -0,0 +1,99 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+use futures::StreamExt;
+use tokio::sync::mpsc;
+
+use crate::{
+    cancel::CancelToken, stages::users::User, store_users, CuteFox, CuteTask, CuteValue,
+    RobberError, SaveMode, SaveReport,
+};
+
+impl CuteFox {
+    /// Executes `task` and stores its users in the database of `CuteFox` while it is
+    /// still fetching.
+    ///
+    /// Chunks go to a writer on a blocking thread through a channel holding a single
+    /// chunk, so fetching waits whenever the writer falls behind. Besides the chunk being
+    /// written, a crash loses the one waiting for the writer and the ones fetched but not
+    /// handed over yet, at most one per worker of the scheduler and one more.
+    /// When fetching fails, the chunks already fetched are stored before the error is returned.
+    ///
+    /// Jobs store their chunks themselves and are refused with `RobberError::JobError`.
+    pub async fn run_pipeline(
+        &self,
+        task: CuteTask,
+        mode: SaveMode,
+    ) -> Result<SaveReport, RobberError> {
+        self.run_pipeline_with(task, mode, CancelToken::new()).await
+    }
+
+    /// Same as `run_pipeline`, but stops once `cancel` is cancelled. The chunks fetched
+    /// until then are stored, then `RobberError::Interrupted` is returned without users.
+    pub async fn run_pipeline_with(
+        &self,
+        task: CuteTask,
+        mode: SaveMode,
+        cancel: CancelToken,
+    ) -> Result<SaveReport, RobberError> {
+        // `RefreshUsers` stores by itself, the writer takes care of it here.
+        let task = match task {
+            CuteTask::RefreshUsers { .. } => {
+                let (user_ids, fields) = self.plan(task).await?;
+                CuteTask::GetUsers { user_ids, fields }
+            }
+            CuteTask::Job { name, .. } => return Err(not_in_pipeline(name)),
+            task => task,
+        };
+
+        let mut conn = self.open_database().await?;
+        let transaction_size = self.transaction_size;
+        let (sender, mut receiver) = mpsc::channel::<Vec<User>>(1);
+
+        let writer = tokio::task::spawn_blocking(move || {
+            let mut report = SaveReport::default();
+            while let Some(users) = receiver.blocking_recv() {
+                let saved = store_users(&mut conn, &users, transaction_size, mode)?;
+                report.saved += saved.saved;
+                report.failed += saved.failed;
+            }
+            Ok::<_, RobberError>(report)
+        });
+
+        let retries = Arc::new(AtomicU64::new(0));
+        let mut chunks = self.indexed_stream(task, cancel, retries.clone());
+        let mut fetched = Ok(());
+        while let Some(value) = chunks.next().await {
+            match value.map(|(_, e)| e) {
+                Ok(CuteValue::Users(users)) => {
+                    // The writer only hangs up when it failed, its error is returned below.
+                    if sender.send(users).await.is_err() {
+                        break;
+                    }
+                }
+                Ok(CuteValue::Job(job)) => {
+                    fetched = Err(not_in_pipeline(job.name));
+                    break;
+                }
+                Err(e) => {
+                    fetched = Err(e);
+                    break;
+                }
+            }
+        }
+        drop(sender);
+
+        let mut report = writer.await.map_err(RobberError::JoinError)??;
+        report.retries = retries.load(Ordering::Relaxed);
+        fetched.map(|_| report)
+    }
+}
+
+fn not_in_pipeline(name: String) -> RobberError {
+    RobberError::JobError {
+        name,
+        message: "cannot be run in a pipeline, jobs store their chunks themselves".to_string(),
+    }
+}
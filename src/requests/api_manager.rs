@@ -1,32 +1,302 @@
-use std::future::Future;
+use std::{
+    cell::Cell,
+    future::Future,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
 
-use reqwest::{Client, Error, Response};
-use serde::{Deserialize, Serialize};
+use bytes::Bytes;
+use reqwest::{Client, Error, Proxy, Response};
+use serde::{de::IgnoredAny, Deserialize, Serialize};
 
 use crate::RobberError;
 
+use super::{
+    errors::VkApiError,
+    health::TokenHealth,
+    rate_limiter::{RateLimiter, USER_REQUESTS_PER_SECOND},
+    retry::RetryPolicy,
+};
+
 pub const API_VERSION: &str = "5.130";
-pub const API_TIMEOUT_MS: u64 = 400;
+
+/// Envelope every VK method wraps its payload in: either `response` or `error`.
+#[derive(Debug, Deserialize)]
+pub struct VkResponse<T> {
+    response: Option<T>,
+    error: Option<VkApiError>,
+}
+
+impl<T> VkResponse<T> {
+    pub fn into_result(self) -> Result<T, RobberError> {
+        match (self.response, self.error) {
+            (_, Some(error)) => Err(RobberError::APIError(error)),
+            (Some(response), None) => Ok(response),
+            (None, None) => Err(RobberError::SerdeError(serde::de::Error::missing_field(
+                "response",
+            ))),
+        }
+    }
+}
+
+tokio::task_local! {
+    /// Retries made inside `count_retries` by the calls of the task running it.
+    static RETRIES: Cell<u64>;
+}
+
+/// Runs `future`, also returning how many retries the calls of any `ApiManager` made
+/// in it. Unlike `ApiStats::retries`, other tasks using the same manager are not counted.
+pub async fn count_retries<F: Future>(future: F) -> (F::Output, u64) {
+    RETRIES
+        .scope(Cell::new(0), async move {
+            let output = future.await;
+            (output, RETRIES.with(Cell::get))
+        })
+        .await
+}
+
+/// Counters kept by every `ApiManager`, for auditing a finished job.
+#[derive(Debug, Default)]
+pub struct ApiStats {
+    calls: AtomicU64,
+    retries: AtomicU64,
+    failures: AtomicU64,
+    users: AtomicU64,
+    requeued: AtomicU64,
+    /// Start of the first call and end of the last one that finished.
+    busy: Mutex<Option<(Instant, Instant)>>,
+}
+
+impl ApiStats {
+    /// Number of calls made, not counting retries.
+    pub fn calls(&self) -> u64 {
+        self.calls.load(Ordering::Relaxed)
+    }
+
+    /// Number of retries made after a retryable failure.
+    pub fn retries(&self) -> u64 {
+        self.retries.load(Ordering::Relaxed)
+    }
+
+    /// Number of calls that still failed after their retries.
+    pub fn failures(&self) -> u64 {
+        self.failures.load(Ordering::Relaxed)
+    }
+
+    /// Number of users fetched by `CuteFox` through this manager.
+    pub fn users(&self) -> u64 {
+        self.users.load(Ordering::Relaxed)
+    }
+
+    /// Number of chunks `CuteFox` handed to another token after they failed on this one.
+    pub fn requeued(&self) -> u64 {
+        self.requeued.load(Ordering::Relaxed)
+    }
+
+    /// Users fetched per second from the start of the first call to the end of the last one,
+    /// so time the manager sat idle before or after its work does not count.
+    pub fn users_per_second(&self) -> f64 {
+        let busy = *self.busy.lock().unwrap_or_else(|e| e.into_inner());
+        let elapsed = busy
+            .map(|(first, last)| last.duration_since(first).as_secs_f64())
+            .unwrap_or(0.0);
+        if elapsed > 0.0 {
+            self.users() as f64 / elapsed
+        } else {
+            0.0
+        }
+    }
+
+    fn call_started(&self) {
+        let now = Instant::now();
+        self.busy
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get_or_insert((now, now));
+    }
+
+    fn call_finished(&self) {
+        if let Some((_, last)) = self.busy.lock().unwrap_or_else(|e| e.into_inner()).as_mut() {
+            *last = Instant::now();
+        }
+    }
+
+    pub(crate) fn add_users(&self, users: usize) {
+        self.users.fetch_add(users as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn add_requeued(&self) {
+        self.requeued.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+pub struct ApiManagerBuilder {
+    token: String,
+    version: String,
+    base_url: String,
+    lang: Option<String>,
+    user_agent: Option<String>,
+    proxy: Option<String>,
+    connect_timeout: Option<Duration>,
+    timeout: Option<Duration>,
+    retry_policy: RetryPolicy,
+    rate_limiter: Option<Arc<RateLimiter>>,
+}
+
+impl ApiManagerBuilder {
+    fn new(token: String) -> Self {
+        Self {
+            token,
+            version: API_VERSION.to_string(),
+            base_url: ApiManager::API_SERVER.to_string(),
+            lang: None,
+            user_agent: None,
+            proxy: None,
+            connect_timeout: None,
+            timeout: None,
+            retry_policy: RetryPolicy::default(),
+            rate_limiter: None,
+        }
+    }
+
+    pub fn version<T: Into<String>>(mut self, version: T) -> Self {
+        self.version = version.into();
+        self
+    }
+
+    /// Server the methods are called on, `https://api.vk.com/method` by default.
+    pub fn base_url<T: Into<String>>(mut self, base_url: T) -> Self {
+        self.base_url = base_url.into();
+        self
+    }
+
+    /// Language of the returned data, sent as the `lang` parameter of every call.
+    pub fn lang<T: Into<String>>(mut self, lang: T) -> Self {
+        self.lang = Some(lang.into());
+        self
+    }
+
+    pub fn user_agent<T: Into<String>>(mut self, user_agent: T) -> Self {
+        self.user_agent = Some(user_agent.into());
+        self
+    }
+
+    /// Proxy URL all requests go through, e.g. `http://127.0.0.1:3128` or `socks5://...`.
+    pub fn proxy<T: Into<String>>(mut self, proxy: T) -> Self {
+        self.proxy = Some(proxy.into());
+        self
+    }
+
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Timeout for a whole request, from connecting until the body is read.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Limiter to use instead of the one registered for the token, e.g. a faster one for a community token.
+    pub fn rate_limiter(mut self, rate_limiter: Arc<RateLimiter>) -> Self {
+        self.rate_limiter = Some(rate_limiter);
+        self
+    }
+
+    pub fn build(self) -> Result<ApiManager, RobberError> {
+        let mut client = Client::builder();
+        if let Some(user_agent) = self.user_agent {
+            client = client.user_agent(user_agent);
+        }
+        if let Some(proxy) = self.proxy {
+            client = client.proxy(Proxy::all(&proxy).map_err(RobberError::ReqwestError)?);
+        }
+        if let Some(timeout) = self.connect_timeout {
+            client = client.connect_timeout(timeout);
+        }
+        if let Some(timeout) = self.timeout {
+            client = client.timeout(timeout);
+        }
+
+        let rate_limiter = match self.rate_limiter {
+            Some(e) => e,
+            None => RateLimiter::registered(&self.token, USER_REQUESTS_PER_SECOND),
+        };
+
+        Ok(ApiManager {
+            token: self.token,
+            version: self.version,
+            base_url: self.base_url.trim_end_matches('/').to_string(),
+            lang: self.lang,
+            client: client.build().map_err(RobberError::ReqwestError)?,
+            retry_policy: self.retry_policy,
+            rate_limiter,
+            stats: ApiStats::default(),
+            health: Mutex::new(TokenHealth::Healthy),
+        })
+    }
+}
 
 pub struct ApiManager {
     token: String,
     version: String,
+    base_url: String,
+    lang: Option<String>,
     client: Client,
+    retry_policy: RetryPolicy,
+    rate_limiter: Arc<RateLimiter>,
+    stats: ApiStats,
+    health: Mutex<TokenHealth>,
 }
 
 impl ApiManager {
     const API_SERVER: &'static str = "https://api.vk.com/method";
 
-    pub fn new<T1, T2>(token: T1, version: T2) -> Self
+    /// Manager with the defaults of `builder`, failing only when the HTTP client cannot be built.
+    pub fn new<T1, T2>(token: T1, version: T2) -> Result<Self, RobberError>
     where
         T1: Into<String>,
         T2: Into<String>,
     {
-        Self {
-            token: token.into(),
-            version: version.into(),
-            client: Client::new(),
-        }
+        Self::builder(token).version(version).build()
+    }
+
+    pub fn builder<T: Into<String>>(token: T) -> ApiManagerBuilder {
+        ApiManagerBuilder::new(token.into())
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    pub fn retry_policy(&self) -> &RetryPolicy {
+        &self.retry_policy
+    }
+
+    pub fn rate_limiter(&self) -> &Arc<RateLimiter> {
+        &self.rate_limiter
+    }
+
+    pub fn stats(&self) -> &ApiStats {
+        &self.stats
+    }
+
+    /// State of the token, updated from the error codes of failed calls.
+    pub fn health(&self) -> TokenHealth {
+        self.health
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+            .current()
     }
 
     pub fn request<T: Serialize + ?Sized>(
@@ -34,14 +304,93 @@ impl ApiManager {
         method: &str,
         params: &T,
     ) -> impl Future<Output = Result<Response, Error>> {
-        let request = self
-            .client
-            .get(format!("{}/{}", ApiManager::API_SERVER, method));
+        let request = self.client.get(format!("{}/{}", self.base_url, method));
 
         let request = request.query(params);
         let request = request.query(&[("access_token", &self.token), ("v", &self.version)]);
+        let request = match &self.lang {
+            Some(lang) => request.query(&[("lang", lang)]),
+            None => request,
+        };
 
-        request.send()
+        let rate_limiter = self.rate_limiter.clone();
+        async move {
+            rate_limiter.acquire().await;
+            request.send().await
+        }
+    }
+
+    async fn request_body<T: Serialize + ?Sized>(
+        &self,
+        method: &str,
+        params: &T,
+    ) -> Result<Bytes, RobberError> {
+        self.request(method, params)
+            .await
+            .map_err(RobberError::ReqwestError)?
+            .bytes()
+            .await
+            .map_err(RobberError::ReqwestError)
+    }
+
+    async fn retrying<Y, F, Fut>(&self, mut attempt: F) -> Result<Y, RobberError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<Y, RobberError>>,
+    {
+        self.stats.calls.fetch_add(1, Ordering::Relaxed);
+        self.stats.call_started();
+
+        let mut retry = 0;
+        loop {
+            match attempt().await {
+                Err(e)
+                    if retry + 1 < self.retry_policy.max_attempts
+                        && self.retry_policy.is_retryable(&e) =>
+                {
+                    tokio::time::sleep(self.retry_policy.delay(retry)).await;
+                    self.stats.retries.fetch_add(1, Ordering::Relaxed);
+                    // Outside of `count_retries` there is nothing to count.
+                    let _ = RETRIES.try_with(|e| e.set(e.get() + 1));
+                    retry += 1;
+                }
+                result => {
+                    self.stats.call_finished();
+                    if let Err(e) = &result {
+                        self.stats.failures.fetch_add(1, Ordering::Relaxed);
+                        let mut current = self.health.lock().unwrap_or_else(|e| e.into_inner());
+                        // An invalid token stays invalid whatever it fails with next.
+                        if let (Some(health), false) = (
+                            TokenHealth::after(e),
+                            matches!(*current, TokenHealth::Invalid { .. }),
+                        ) {
+                            *current = health;
+                        }
+                    }
+                    return result;
+                }
+            }
+        }
+    }
+
+    async fn request_json_once<T: Serialize + ?Sized, Y>(
+        &self,
+        method: &str,
+        params: &T,
+    ) -> Result<Y, RobberError>
+    where
+        Y: for<'de> Deserialize<'de>,
+    {
+        let body = self.request_body(method, params).await?;
+
+        if let Ok(VkResponse::<IgnoredAny> {
+            error: Some(error), ..
+        }) = serde_json::from_slice(&body)
+        {
+            return Err(RobberError::APIError(error));
+        }
+
+        serde_json::from_slice(&body).map_err(RobberError::SerdeError)
     }
 
     pub async fn request_json<'a, T: Serialize + ?Sized, Y>(
@@ -52,12 +401,26 @@ impl ApiManager {
     where
         Y: for<'de> Deserialize<'de>,
     {
-        Ok(self
-            .request(method, params)
+        self.retrying(|| self.request_json_once(method, params))
             .await
-            .map_err(RobberError::ReqwestError)?
-            .json::<Y>()
-            .await
-            .map_err(RobberError::ReqwestError)?)
+    }
+
+    /// Calls `method` and unwraps its `response` payload, turning `error` into `RobberError::APIError`.
+    pub async fn call<Y>(
+        &self,
+        method: &str,
+        params: &(impl Serialize + ?Sized),
+    ) -> Result<Y, RobberError>
+    where
+        Y: for<'de> Deserialize<'de>,
+    {
+        self.retrying(|| async {
+            let body = self.request_body(method, params).await?;
+
+            serde_json::from_slice::<VkResponse<Y>>(&body)
+                .map_err(RobberError::SerdeError)?
+                .into_result()
+        })
+        .await
     }
 }
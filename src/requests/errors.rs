@@ -0,0 +1,110 @@
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RequestParam {
+    pub key: String,
+    pub value: String,
+}
+
+/// Raw `error` object returned by VK instead of `response`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ErrorObject {
+    pub error_code: i64,
+    pub error_msg: String,
+
+    #[serde(default)]
+    pub request_params: Vec<RequestParam>,
+
+    pub captcha_sid: Option<String>,
+    pub captcha_img: Option<String>,
+}
+
+macro_rules! vk_api_errors {
+    ($($(#[$meta:meta])* $variant:ident = $code:literal),* $(,)?) => {
+        #[derive(Debug, Clone, Deserialize)]
+        #[serde(from = "ErrorObject")]
+        pub enum VkApiError {
+            $($(#[$meta])* $variant(ErrorObject),)*
+            /// Any error code without a dedicated variant.
+            Other(ErrorObject),
+        }
+
+        impl From<ErrorObject> for VkApiError {
+            fn from(error: ErrorObject) -> Self {
+                match error.error_code {
+                    $($code => VkApiError::$variant(error),)*
+                    _ => VkApiError::Other(error),
+                }
+            }
+        }
+
+        impl VkApiError {
+            pub fn details(&self) -> &ErrorObject {
+                match self {
+                    $(VkApiError::$variant(e))|* | VkApiError::Other(e) => e,
+                }
+            }
+        }
+    };
+}
+
+vk_api_errors! {
+    /// 1: unknown error occurred.
+    Unknown = 1,
+    /// 2: application is disabled.
+    AppDisabled = 2,
+    /// 3: unknown method passed.
+    UnknownMethod = 3,
+    /// 5: user authorization failed, usually an invalid or revoked token.
+    AuthFailed = 5,
+    /// 6: too many requests per second.
+    TooManyRequests = 6,
+    /// 7: permission to perform this action is denied.
+    PermissionDenied = 7,
+    /// 8: invalid request.
+    InvalidRequest = 8,
+    /// 9: flood control.
+    FloodControl = 9,
+    /// 10: internal server error.
+    InternalServerError = 10,
+    /// 14: captcha needed, see `captcha_sid` and `captcha_img`.
+    CaptchaNeeded = 14,
+    /// 15: access denied.
+    AccessDenied = 15,
+    /// 18: page deleted or banned.
+    UserDeleted = 18,
+    /// 29: rate limit reached, the daily quota of the method is exhausted.
+    RateLimitReached = 29,
+    /// 30: this profile is private.
+    PrivateProfile = 30,
+    /// 100: one of the parameters specified was missing or invalid.
+    InvalidParams = 100,
+    /// 113: invalid user id.
+    InvalidUserId = 113,
+    /// 125: invalid group id.
+    InvalidGroupId = 125,
+    /// 203: access to the group is denied.
+    GroupAccessDenied = 203,
+}
+
+impl VkApiError {
+    pub fn code(&self) -> i64 {
+        self.details().error_code
+    }
+
+    pub fn message(&self) -> &str {
+        &self.details().error_msg
+    }
+
+    pub fn request_params(&self) -> &[RequestParam] {
+        &self.details().request_params
+    }
+}
+
+impl std::fmt::Display for VkApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} (code {})", self.message(), self.code())
+    }
+}
+
+impl std::error::Error for VkApiError {}
@@ -0,0 +1,81 @@
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+use crate::RobberError;
+
+use super::errors::VkApiError;
+
+/// How long a token rests after VK error 29, the daily quota of a method.
+pub const DAILY_LIMIT_COOLDOWN: Duration = Duration::from_secs(3600);
+/// How long a token rests after flood control or a captcha request.
+pub const FLOOD_COOLDOWN: Duration = Duration::from_secs(60);
+/// How long a token rests after error 6 outlived its retries.
+pub const TOO_MANY_REQUESTS_COOLDOWN: Duration = Duration::from_secs(1);
+
+/// State of an access token, as seen from the errors VK returned for it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TokenHealth {
+    Healthy,
+    /// The token may be used again from `until`.
+    RateLimited {
+        until: Instant,
+        reason: String,
+    },
+    /// The token was revoked or its application disabled, it will not work again.
+    Invalid {
+        reason: String,
+    },
+}
+
+impl TokenHealth {
+    /// State `error` puts a token in, or `None` when it says nothing about the token.
+    pub fn after(error: &RobberError) -> Option<TokenHealth> {
+        let error = match error {
+            RobberError::APIError(e) => e,
+            _ => return None,
+        };
+        let rate_limited = |cooldown| TokenHealth::RateLimited {
+            until: Instant::now() + cooldown,
+            reason: error.to_string(),
+        };
+
+        match error {
+            VkApiError::AuthFailed(_) | VkApiError::AppDisabled(_) => Some(TokenHealth::Invalid {
+                reason: error.to_string(),
+            }),
+            VkApiError::RateLimitReached(_) => Some(rate_limited(DAILY_LIMIT_COOLDOWN)),
+            VkApiError::FloodControl(_) | VkApiError::CaptchaNeeded(_) => {
+                Some(rate_limited(FLOOD_COOLDOWN))
+            }
+            VkApiError::TooManyRequests(_) => Some(rate_limited(TOO_MANY_REQUESTS_COOLDOWN)),
+            _ => None,
+        }
+    }
+
+    /// Same state, with a rate limit that ran out turned back into `Healthy`.
+    pub(crate) fn current(self) -> TokenHealth {
+        match self {
+            TokenHealth::RateLimited { until, .. } if until <= Instant::now() => {
+                TokenHealth::Healthy
+            }
+            health => health,
+        }
+    }
+}
+
+impl fmt::Display for TokenHealth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenHealth::Healthy => write!(f, "healthy"),
+            TokenHealth::RateLimited { until, reason } => write!(
+                f,
+                "rate limited for {}s: {}",
+                until.saturating_duration_since(Instant::now()).as_secs(),
+                reason
+            ),
+            TokenHealth::Invalid { reason } => write!(f, "disabled: {}", reason),
+        }
+    }
+}
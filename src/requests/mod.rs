@@ -1 +1,5 @@
 pub mod api_manager;
+pub mod errors;
+pub mod health;
+pub mod rate_limiter;
+pub mod retry;
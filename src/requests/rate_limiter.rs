@@ -0,0 +1,141 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, OnceLock},
+    time::{Duration, Instant},
+};
+
+/// VK limit for methods called with a user access token.
+pub const USER_REQUESTS_PER_SECOND: u32 = 3;
+/// VK limit for methods called with a community access token.
+pub const GROUP_REQUESTS_PER_SECOND: u32 = 20;
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+use crate::RobberError;
+
+/// Token bucket shared by every request made with one access token.
+///
+/// Callers reserve a token and sleep until it is available, so waiting tasks
+/// are served in the order they asked. The bucket holds a single token by
+/// default, which spaces requests evenly and keeps any one-second window
+/// within the limit.
+pub struct RateLimiter {
+    requests_per_second: u32,
+    capacity: f64,
+    bucket: Mutex<Bucket>,
+}
+
+impl RateLimiter {
+    pub fn new(requests_per_second: u32) -> Self {
+        Self::with_burst(requests_per_second, 1)
+    }
+
+    pub fn with_burst(requests_per_second: u32, burst: u32) -> Self {
+        let capacity = burst.max(1) as f64;
+
+        Self {
+            requests_per_second: requests_per_second.max(1),
+            capacity,
+            bucket: Mutex::new(Bucket {
+                tokens: capacity,
+                updated: Instant::now(),
+            }),
+        }
+    }
+
+    /// Limiter registered for `token`, created on first use.
+    ///
+    /// Every `ApiManager` built for the same token gets the same limiter, so
+    /// the limit holds however many managers and tasks share it. The rate of a
+    /// registered limiter never changes: asking for the token with another rate
+    /// fails with `RobberError::ConfigError` instead of altering existing managers.
+    pub fn for_token(
+        token: &str,
+        requests_per_second: u32,
+    ) -> Result<Arc<RateLimiter>, RobberError> {
+        let limiter = Self::registered(token, requests_per_second);
+        if limiter.requests_per_second() != requests_per_second.max(1) {
+            return Err(RobberError::ConfigError {
+                key: "requests_per_second".to_string(),
+                message: format!(
+                    "the token is already limited to {} requests per second",
+                    limiter.requests_per_second()
+                ),
+            });
+        }
+        Ok(limiter)
+    }
+
+    /// Limiter registered for `token`, created with `requests_per_second` when
+    /// there is none yet and left at its current rate otherwise.
+    pub(crate) fn registered(token: &str, requests_per_second: u32) -> Arc<RateLimiter> {
+        static LIMITERS: OnceLock<Mutex<HashMap<String, Arc<RateLimiter>>>> = OnceLock::new();
+
+        let mut limiters = LIMITERS
+            .get_or_init(Default::default)
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+
+        limiters
+            .entry(token.to_string())
+            .or_insert_with(|| Arc::new(RateLimiter::new(requests_per_second)))
+            .clone()
+    }
+
+    pub fn requests_per_second(&self) -> u32 {
+        self.requests_per_second
+    }
+
+    fn reserve(&self) -> Duration {
+        let mut bucket = self.bucket.lock().unwrap_or_else(|e| e.into_inner());
+        let rate = self.requests_per_second() as f64;
+
+        let now = Instant::now();
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * rate).min(self.capacity);
+        bucket.updated = now;
+
+        bucket.tokens -= 1.0;
+        if bucket.tokens >= 0.0 {
+            Duration::from_secs(0)
+        } else {
+            Duration::from_secs_f64(-bucket.tokens / rate)
+        }
+    }
+
+    /// Time until a request could be sent without waiting, reserving nothing.
+    pub fn available_in(&self) -> Duration {
+        let bucket = self.bucket.lock().unwrap_or_else(|e| e.into_inner());
+        let rate = self.requests_per_second() as f64;
+
+        let elapsed = bucket.updated.elapsed().as_secs_f64();
+        let tokens = (bucket.tokens + elapsed * rate).min(self.capacity);
+        if tokens >= 1.0 {
+            Duration::from_secs(0)
+        } else {
+            Duration::from_secs_f64((1.0 - tokens) / rate)
+        }
+    }
+
+    /// Waits until a request could be sent without waiting, reserving nothing.
+    pub async fn ready(&self) {
+        loop {
+            let wait = self.available_in();
+            if wait == Duration::from_secs(0) {
+                return;
+            }
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Waits until one more request may be sent.
+    pub async fn acquire(&self) {
+        let wait = self.reserve();
+        if wait > Duration::from_secs(0) {
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
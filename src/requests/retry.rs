@@ -0,0 +1,118 @@
+use std::time::Duration;
+
+use rand::Rng;
+
+use crate::RobberError;
+
+use super::errors::VkApiError;
+
+/// Families of failures a `RetryPolicy` can choose to retry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorClass {
+    /// Connection failures, timeouts and broken bodies reported by reqwest.
+    Transport,
+    /// VK error 6: too many requests per second.
+    TooManyRequests,
+    /// VK error 9: flood control.
+    FloodControl,
+    /// VK errors 1 and 10: unknown or internal server error.
+    Server,
+}
+
+impl ErrorClass {
+    pub fn of(error: &RobberError) -> Option<ErrorClass> {
+        match error {
+            RobberError::ReqwestError(e)
+                if e.is_timeout() || e.is_connect() || e.is_request() || e.is_body() =>
+            {
+                Some(ErrorClass::Transport)
+            }
+            RobberError::APIError(VkApiError::TooManyRequests(_)) => {
+                Some(ErrorClass::TooManyRequests)
+            }
+            RobberError::APIError(VkApiError::FloodControl(_)) => Some(ErrorClass::FloodControl),
+            RobberError::APIError(VkApiError::Unknown(_))
+            | RobberError::APIError(VkApiError::InternalServerError(_)) => Some(ErrorClass::Server),
+            _ => None,
+        }
+    }
+}
+
+/// Exponential backoff with jitter applied by `ApiManager` to every call.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first one.
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled on every following one.
+    pub base_delay: Duration,
+    /// Upper bound for a single delay, before jitter.
+    pub max_delay: Duration,
+    /// Random spread applied to every delay, as a fraction of it, set by `with_jitter`.
+    jitter: f64,
+    pub retry_on: Vec<ErrorClass>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+            jitter: 0.2,
+            retry_on: vec![
+                ErrorClass::Transport,
+                ErrorClass::TooManyRequests,
+                ErrorClass::Server,
+            ],
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Policy that gives up after the first failure.
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            retry_on: Vec::new(),
+            ..Default::default()
+        }
+    }
+
+    /// Spreads every delay randomly by up to `jitter` of it, which has to be in `0.0..=1.0`.
+    pub fn with_jitter(mut self, jitter: f64) -> Result<Self, RobberError> {
+        if !(0.0..=1.0).contains(&jitter) {
+            return Err(RobberError::ConfigError {
+                key: "jitter".to_string(),
+                message: format!("expected a fraction from 0 to 1, found {}", jitter),
+            });
+        }
+        self.jitter = jitter;
+        Ok(self)
+    }
+
+    pub fn jitter(&self) -> f64 {
+        self.jitter
+    }
+
+    pub fn is_retryable(&self, error: &RobberError) -> bool {
+        match ErrorClass::of(error) {
+            Some(class) => self.retry_on.contains(&class),
+            None => false,
+        }
+    }
+
+    /// Delay to wait before retry number `retry` (starting from zero).
+    pub fn delay(&self, retry: u32) -> Duration {
+        let delay = self
+            .base_delay
+            .checked_mul(1u32.checked_shl(retry).unwrap_or(u32::MAX))
+            .unwrap_or(self.max_delay)
+            .min(self.max_delay);
+
+        if self.jitter == 0.0 {
+            return delay;
+        }
+
+        delay.mul_f64(1.0 + rand::thread_rng().gen_range(-self.jitter..=self.jitter))
+    }
+}
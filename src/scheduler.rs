@@ -0,0 +1,388 @@
+use std::{
+    collections::VecDeque,
+    iter::Peekable,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use futures::{
+    future,
+    stream::{self, BoxStream},
+    StreamExt,
+};
+use tokio::sync::{mpsc, watch};
+
+use crate::{
+    cancel::CancelToken,
+    requests::{
+        api_manager::{count_retries, ApiManager},
+        health::TokenHealth,
+    },
+    stages::{
+        fields::FieldSet,
+        users::{User, UserInteraction},
+    },
+    CuteValue, RobberError,
+};
+
+/// Longest a manager waits for its token to recover. A token rate limited for longer,
+/// e.g. after the daily quota of VK error 29, takes no more chunks of this run.
+pub const MAX_RATE_LIMIT_WAIT: Duration = Duration::from_secs(600);
+
+/// Chunks given to `fetch_users` no worker has taken yet.
+type Pending = Peekable<Box<dyn Iterator<Item = Vec<i32>> + Send>>;
+
+struct Chunk {
+    /// Position of the chunk in the chunks given to `fetch_users`.
+    index: usize,
+    user_ids: Vec<i32>,
+    /// Managers this chunk already failed on.
+    tried: Vec<usize>,
+    /// Last failure, returned once no manager is left to try.
+    error: Option<RobberError>,
+}
+
+struct Queue {
+    /// Chunks that failed or had to rest and came back.
+    chunks: VecDeque<Chunk>,
+    /// Chunks never taken yet, read only when a worker asks for one.
+    pending: Pending,
+    /// Position of the next chunk of `pending`.
+    next: usize,
+    /// Chunks taken and not finished yet.
+    running: usize,
+    /// Managers whose token turned out to be invalid or rate limited for too long.
+    disabled: Vec<bool>,
+}
+
+impl Queue {
+    fn has_candidate(&self, chunk: &Chunk) -> bool {
+        (0..self.disabled.len()).any(|i| !self.disabled[i] && !chunk.tried.contains(&i))
+    }
+
+    fn next_pending(&mut self) -> Option<Chunk> {
+        let user_ids = self.pending.next()?;
+        let index = self.next;
+        self.next += 1;
+        Some(Chunk {
+            index,
+            user_ids,
+            tried: Vec::new(),
+            error: None,
+        })
+    }
+
+    fn is_empty(&mut self) -> bool {
+        self.chunks.is_empty() && self.pending.peek().is_none()
+    }
+}
+
+/// Chunks shared by the workers of every manager.
+struct Shared {
+    queue: Mutex<Queue>,
+    /// Bumped whenever a chunk finishes or comes back, waking idle workers.
+    changed: watch::Sender<()>,
+    /// Retries made by the calls of every worker.
+    retries: Arc<AtomicU64>,
+}
+
+enum Next {
+    Chunk(Chunk),
+    Wait,
+    Done,
+}
+
+fn no_tokens_left() -> RobberError {
+    RobberError::TokenError {
+        message: "every access token is disabled or rate limited".to_string(),
+    }
+}
+
+impl Shared {
+    fn update<T>(&self, f: impl FnOnce(&mut Queue) -> T) -> T {
+        let result = f(&mut self.queue.lock().unwrap_or_else(|e| e.into_inner()));
+        // Fails only once every worker is gone.
+        let _ = self.changed.send(());
+        result
+    }
+
+    /// First chunk that came back and `manager` has not failed on yet, or else the
+    /// next pending one.
+    fn take(&self, manager: usize) -> Next {
+        let mut queue = self.queue.lock().unwrap_or_else(|e| e.into_inner());
+
+        let chunk = match queue
+            .chunks
+            .iter()
+            .position(|chunk| !chunk.tried.contains(&manager))
+        {
+            Some(i) => queue.chunks.remove(i),
+            None => queue.next_pending(),
+        };
+        match chunk {
+            Some(chunk) => {
+                queue.running += 1;
+                Next::Chunk(chunk)
+            }
+            // Running chunks may still fail and come back.
+            None if queue.running > 0 || !queue.chunks.is_empty() => Next::Wait,
+            None => Next::Done,
+        }
+    }
+
+    fn is_done(&self) -> bool {
+        let mut queue = self.queue.lock().unwrap_or_else(|e| e.into_inner());
+        queue.running == 0 && queue.is_empty()
+    }
+
+    fn finish(&self) {
+        self.update(|queue| queue.running -= 1)
+    }
+
+    /// Puts back a chunk that failed only because its manager has to rest.
+    fn retry(&self, chunk: Chunk) {
+        self.update(|queue| {
+            queue.running -= 1;
+            queue.chunks.push_back(chunk);
+        })
+    }
+
+    /// Puts `chunk` back for the managers that have not tried it, or returns
+    /// `error` when none is left.
+    fn fail(&self, mut chunk: Chunk, manager: usize, error: RobberError) -> Option<RobberError> {
+        self.update(|queue| {
+            queue.running -= 1;
+            chunk.tried.push(manager);
+
+            if queue.has_candidate(&chunk) {
+                chunk.error = Some(error);
+                queue.chunks.push_back(chunk);
+                None
+            } else {
+                Some(error)
+            }
+        })
+    }
+
+    /// Quarantines `manager`, returning the errors of the chunks no manager is left for.
+    fn disable(&self, manager: usize) -> Vec<RobberError> {
+        self.update(|queue| {
+            queue.disabled[manager] = true;
+
+            let mut chunks: Vec<Chunk> = queue.chunks.drain(..).collect();
+            // Pending chunks are only failed once no manager is left for any of them.
+            if queue.disabled.iter().all(|e| *e) {
+                while let Some(chunk) = queue.next_pending() {
+                    chunks.push(chunk);
+                }
+            }
+            let mut errors = Vec::new();
+            for chunk in chunks {
+                if queue.has_candidate(&chunk) {
+                    queue.chunks.push_back(chunk);
+                } else {
+                    errors.push(chunk.error.unwrap_or_else(no_tokens_left));
+                }
+            }
+            errors
+        })
+    }
+}
+
+/// Fetches `chunks` with `slots` workers per manager, yielding them as they finish.
+/// A chunk is only read from `chunks` once a worker takes it.
+///
+/// A worker only takes a chunk once the rate limiter of its manager has room, so
+/// faster tokens take more of the work. A chunk failing on one manager goes back to
+/// the queue for the others, and is returned as an error once every manager failed it.
+/// Managers whose token is rate limited rest until it may be used again, unless that is
+/// further away than `MAX_RATE_LIMIT_WAIT`. Those and managers whose token is invalid stop
+/// taking chunks, and once no manager is left every remaining chunk fails with
+/// `RobberError::TokenError`.
+///
+/// Besides the chunk waiting to be taken from the stream, each worker holds at most the
+/// one it fetched last until it is taken.
+///
+/// Retries of the calls made for the chunks, failed ones included, are added to `retries`.
+///
+/// Users come with the position of their chunk in `chunks`. Once `cancel` is cancelled
+/// no chunk is taken anymore, the stream ends after the requests in flight with
+/// `RobberError::Interrupted` if chunks were left. It holds no users, every fetched
+/// chunk was yielded before.
+pub(crate) fn fetch_users<I>(
+    managers: Arc<Vec<Arc<ApiManager>>>,
+    chunks: I,
+    fields: Arc<FieldSet>,
+    slots: usize,
+    cancel: CancelToken,
+    retries: Arc<AtomicU64>,
+) -> BoxStream<'static, Result<(usize, Vec<User>), RobberError>>
+where
+    I: IntoIterator<Item = Vec<i32>>,
+    I::IntoIter: Send + 'static,
+{
+    let chunks: Box<dyn Iterator<Item = Vec<i32>> + Send> = Box::new(chunks.into_iter());
+    let mut pending = chunks.peekable();
+    if pending.peek().is_none() {
+        return stream::empty().boxed();
+    }
+
+    // Workers are started on first poll, so an unpolled stream costs nothing.
+    stream::once(async move {
+        // Workers wait with the chunk they fetched until this one was taken, so a slow
+        // consumer holds up fetching instead of chunks piling up here.
+        let (sender, receiver) = mpsc::channel(1);
+        let (changed, watcher) = watch::channel(());
+        let shared = Arc::new(Shared {
+            queue: Mutex::new(Queue {
+                chunks: VecDeque::new(),
+                pending,
+                next: 0,
+                running: 0,
+                disabled: vec![false; managers.len()],
+            }),
+            changed,
+            retries,
+        });
+
+        for (i, manager) in managers.iter().enumerate() {
+            for _ in 0..slots {
+                tokio::spawn(worker(
+                    i,
+                    manager.clone(),
+                    shared.clone(),
+                    fields.clone(),
+                    watcher.clone(),
+                    cancel.clone(),
+                    sender.clone(),
+                ));
+            }
+        }
+
+        let interrupted = stream::once(async move {
+            // Workers only leave chunks behind when they were cancelled.
+            if shared.is_done() {
+                None
+            } else {
+                Some(Err(RobberError::Interrupted(Box::new(CuteValue::Users(
+                    Vec::new(),
+                )))))
+            }
+        })
+        .filter_map(future::ready);
+        stream::unfold(receiver, |mut receiver| async move {
+            receiver.recv().await.map(|e| (e, receiver))
+        })
+        .chain(interrupted)
+    })
+    .flatten()
+    .boxed()
+}
+
+async fn worker(
+    index: usize,
+    manager: Arc<ApiManager>,
+    shared: Arc<Shared>,
+    fields: Arc<FieldSet>,
+    mut changed: watch::Receiver<()>,
+    cancel: CancelToken,
+    sender: mpsc::Sender<Result<(usize, Vec<User>), RobberError>>,
+) {
+    while !sender.is_closed() && !cancel.is_cancelled() {
+        match manager.health() {
+            TokenHealth::Healthy => {}
+            TokenHealth::RateLimited { until, .. }
+                if until > Instant::now() + MAX_RATE_LIMIT_WAIT =>
+            {
+                disable(index, &shared, &sender).await;
+                return;
+            }
+            // Rests until the token recovers, unless the other managers finish first.
+            TokenHealth::RateLimited { until, .. } => {
+                if shared.is_done() {
+                    return;
+                }
+                tokio::select! {
+                    _ = tokio::time::sleep_until(until.into()) => {}
+                    _ = changed.changed() => {}
+                    _ = sender.closed() => return,
+                    _ = cancel.cancelled() => return,
+                }
+                continue;
+            }
+            TokenHealth::Invalid { .. } => {
+                disable(index, &shared, &sender).await;
+                return;
+            }
+        }
+        tokio::select! {
+            _ = manager.rate_limiter().ready() => {}
+            _ = cancel.cancelled() => return,
+        }
+
+        let chunk = match shared.take(index) {
+            Next::Chunk(e) => e,
+            // A change since the last wait wakes this one right away, so none is missed.
+            Next::Wait => {
+                tokio::select! {
+                    result = changed.changed() => if result.is_err() {
+                        return;
+                    },
+                    _ = cancel.cancelled() => return,
+                }
+                continue;
+            }
+            Next::Done => return,
+        };
+
+        let (result, retries) =
+            count_retries(manager.get_users_chunk(&chunk.user_ids, &fields)).await;
+        shared.retries.fetch_add(retries, Ordering::Relaxed);
+        let result = match result {
+            Ok(users) => {
+                manager.stats().add_users(users.len());
+                shared.finish();
+                Ok((chunk.index, users))
+            }
+            Err(e) => {
+                let e = match manager.health() {
+                    TokenHealth::Healthy => e,
+                    TokenHealth::RateLimited { .. } => {
+                        manager.stats().add_requeued();
+                        shared.retry(chunk);
+                        continue;
+                    }
+                    // Only the token is to blame, like for the chunks it will not take anymore.
+                    TokenHealth::Invalid { .. } => no_tokens_left(),
+                };
+                match shared.fail(chunk, index, e) {
+                    Some(e) => Err(e),
+                    None => {
+                        manager.stats().add_requeued();
+                        continue;
+                    }
+                }
+            }
+        };
+
+        if sender.send(result).await.is_err() {
+            return;
+        }
+    }
+}
+
+/// Stops giving chunks to `manager`, sending the errors of the chunks no manager is left for.
+async fn disable(
+    manager: usize,
+    shared: &Shared,
+    sender: &mpsc::Sender<Result<(usize, Vec<User>), RobberError>>,
+) {
+    for error in shared.disable(manager) {
+        if sender.send(Err(error)).await.is_err() {
+            break;
+        }
+    }
+}
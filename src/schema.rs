@@ -0,0 +1,270 @@
+use rusqlite::Connection;
+
+use crate::RobberError;
+
+/// Tables written by `User::store`, in the order they are created.
+pub const TABLES: &[&str] = &[
+    "objects",
+    "career",
+    "city",
+    "contacts",
+    "counters",
+    "country",
+    "education",
+    "last_seen",
+    "military",
+    "occupation",
+    "personal",
+    "relation_partner",
+    "relatives",
+    "schools",
+    "schools_type",
+    "universities",
+];
+
+/// Users `SqliteStorage::save_with` could not store in `SaveMode::RecordErrors`.
+pub const STORE_ERRORS_TABLE: &str = "store_errors";
+
+/// Resumable jobs of `CuteTask::Job` and the chunks of user ids they are split into.
+pub const JOBS_TABLE: &str = "jobs";
+pub const JOB_CHUNKS_TABLE: &str = "job_chunks";
+
+/// Options stored with the database, such as whether `history` is enabled.
+pub const SETTINGS_TABLE: &str = "settings";
+
+const SCHEMA: &str = r#"
+CREATE TABLE IF NOT EXISTS "objects" (
+    "id" INTEGER NOT NULL UNIQUE,
+    "first_name" TEXT NOT NULL,
+    "last_name" TEXT NOT NULL,
+    "deactivated" TEXT,
+    "is_closed" INTEGER,
+    "about" TEXT,
+    "activities" TEXT,
+    "bdate" TEXT,
+    "books" TEXT,
+    "domain" TEXT,
+    "followers_count" INTEGER,
+    "games" TEXT,
+    "has_mobile" INTEGER,
+    "has_photo" INTEGER,
+    "home_town" TEXT,
+    "interests" TEXT,
+    "maiden_name" TEXT,
+    "movies" TEXT,
+    "music" TEXT,
+    "nickname" TEXT,
+    "photo_max_orig" TEXT,
+    "quotes" TEXT,
+    "screen_name" TEXT,
+    "sex" INTEGER,
+    "site" TEXT,
+    "status" TEXT,
+    "tv" TEXT,
+    "verified" TEXT,
+    "skype" TEXT,
+    "facebook" TEXT,
+    "twitter" TEXT,
+    "livejournal" TEXT,
+    "instagram" TEXT,
+    "relation" INTEGER,
+    "bdate_day" INTEGER,
+    "bdate_month" INTEGER,
+    "bdate_year" INTEGER,
+    "fetched_at" INTEGER,
+    PRIMARY KEY("id")
+);
+
+CREATE TABLE IF NOT EXISTS "career" (
+    "user_id" INTEGER NOT NULL,
+    "group_id" INTEGER,
+    "company" TEXT,
+    "country_id" INTEGER,
+    "city_id" INTEGER,
+    "city_name" TEXT,
+    "from" INTEGER,
+    "until" INTEGER,
+    "position" TEXT
+);
+CREATE INDEX IF NOT EXISTS "career_user_id" ON "career" ("user_id");
+
+CREATE TABLE IF NOT EXISTS "city" (
+    "user_id" INTEGER NOT NULL UNIQUE,
+    "id" INTEGER,
+    PRIMARY KEY("user_id")
+);
+
+CREATE TABLE IF NOT EXISTS "contacts" (
+    "user_id" INTEGER NOT NULL UNIQUE,
+    "mobile_phone" TEXT,
+    "home_phone" TEXT,
+    PRIMARY KEY("user_id")
+);
+
+CREATE TABLE IF NOT EXISTS "counters" (
+    "user_id" INTEGER NOT NULL UNIQUE,
+    "albums" INTEGER,
+    "videos" INTEGER,
+    "audios" INTEGER,
+    "photos" INTEGER,
+    "notes" INTEGER,
+    "friends" INTEGER,
+    "groups" INTEGER,
+    "user_videos" INTEGER,
+    "followers" INTEGER,
+    "pages" INTEGER,
+    PRIMARY KEY("user_id")
+);
+
+CREATE TABLE IF NOT EXISTS "country" (
+    "user_id" INTEGER NOT NULL UNIQUE,
+    "id" INTEGER,
+    PRIMARY KEY("user_id")
+);
+
+CREATE TABLE IF NOT EXISTS "education" (
+    "user_id" INTEGER NOT NULL,
+    "university" INTEGER,
+    "university_name" TEXT,
+    "faculty" INTEGER,
+    "faculty_name" TEXT,
+    "graduation" INTEGER,
+    PRIMARY KEY("user_id")
+);
+
+CREATE TABLE IF NOT EXISTS "last_seen" (
+    "user_id" INTEGER NOT NULL UNIQUE,
+    "time" INTEGER,
+    "platform" INTEGER,
+    PRIMARY KEY("user_id")
+);
+
+CREATE TABLE IF NOT EXISTS "military" (
+    "user_id" INTEGER NOT NULL,
+    "unit" TEXT,
+    "unit_id" INTEGER,
+    "country_id" INTEGER,
+    "from" INTEGER,
+    "until" INTEGER
+);
+CREATE INDEX IF NOT EXISTS "military_user_id" ON "military" ("user_id");
+
+CREATE TABLE IF NOT EXISTS "occupation" (
+    "user_id" INTEGER NOT NULL UNIQUE,
+    "type" TEXT,
+    "id" INTEGER,
+    "name" TEXT,
+    PRIMARY KEY("user_id")
+);
+
+CREATE TABLE IF NOT EXISTS "personal" (
+    "user_id" INTEGER NOT NULL UNIQUE,
+    "political" INTEGER,
+    "langs" TEXT,
+    "religion" TEXT,
+    "inspired_by" TEXT,
+    "people_main" INTEGER,
+    "life_main" INTEGER,
+    "smoking" INTEGER,
+    "alcohol" INTEGER,
+    PRIMARY KEY("user_id")
+);
+
+CREATE TABLE IF NOT EXISTS "relation_partner" (
+    "user_id" INTEGER NOT NULL UNIQUE,
+    "id" INTEGER,
+    "first_name" TEXT,
+    "last_name" TEXT,
+    PRIMARY KEY("user_id")
+);
+
+CREATE TABLE IF NOT EXISTS "relatives" (
+    "user_id" INTEGER NOT NULL,
+    "id" INTEGER,
+    "name" TEXT,
+    "type" TEXT
+);
+CREATE INDEX IF NOT EXISTS "relatives_user_id" ON "relatives" ("user_id");
+
+CREATE TABLE IF NOT EXISTS "schools" (
+    "user_id" INTEGER NOT NULL,
+    "id" INTEGER,
+    "country" INTEGER,
+    "city" INTEGER,
+    "name" TEXT,
+    "year_from" INTEGER,
+    "year_to" INTEGER,
+    "year_graduated" INTEGER,
+    "class" TEXT,
+    "speciality" TEXT,
+    "type" INTEGER
+);
+CREATE INDEX IF NOT EXISTS "schools_user_id" ON "schools" ("user_id");
+
+CREATE TABLE IF NOT EXISTS "schools_type" (
+    "type" INTEGER NOT NULL UNIQUE,
+    "type_str" TEXT NOT NULL,
+    PRIMARY KEY("type")
+);
+
+CREATE TABLE IF NOT EXISTS "universities" (
+    "user_id" INTEGER NOT NULL,
+    "id" INTEGER,
+    "country" INTEGER,
+    "city" INTEGER,
+    "name" TEXT,
+    "faculty" INTEGER,
+    "faculty_name" TEXT,
+    "chair" INTEGER,
+    "chair_name" TEXT,
+    "graduation" INTEGER,
+    "education_form" TEXT,
+    "education_status" TEXT
+);
+CREATE INDEX IF NOT EXISTS "universities_user_id" ON "universities" ("user_id");
+
+CREATE TABLE IF NOT EXISTS "store_errors" (
+    "user_id" INTEGER NOT NULL,
+    "table" TEXT NOT NULL,
+    "message" TEXT NOT NULL,
+    "payload" TEXT,
+    "failed_at" INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS "jobs" (
+    "name" TEXT NOT NULL UNIQUE,
+    "fields" TEXT NOT NULL,
+    "created_at" INTEGER NOT NULL,
+    "finished_at" INTEGER,
+    "retries" INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY("name")
+);
+
+CREATE TABLE IF NOT EXISTS "job_chunks" (
+    "job" TEXT NOT NULL,
+    "chunk" INTEGER NOT NULL,
+    "user_ids" TEXT NOT NULL,
+    "done_at" INTEGER,
+    PRIMARY KEY("job", "chunk")
+);
+CREATE INDEX IF NOT EXISTS "job_chunks_pending" ON "job_chunks" ("job", "done_at");
+
+CREATE TABLE IF NOT EXISTS "settings" (
+    "key" TEXT NOT NULL UNIQUE,
+    "value" TEXT NOT NULL,
+    PRIMARY KEY("key")
+);
+
+CREATE TABLE IF NOT EXISTS "user_history" (
+    "user_id" INTEGER NOT NULL,
+    "snapshot" TEXT NOT NULL,
+    "valid_from" INTEGER NOT NULL,
+    "valid_to" INTEGER
+);
+CREATE INDEX IF NOT EXISTS "user_history_user_id" ON "user_history" ("user_id", "valid_to");
+"#;
+
+/// Creates every table and index `User::store` writes to. Existing tables are left untouched.
+pub fn create(conn: &Connection) -> Result<(), RobberError> {
+    conn.execute_batch(SCHEMA).map_err(RobberError::SqliteError)
+}
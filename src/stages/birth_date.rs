@@ -0,0 +1,135 @@
+use std::{fmt, str::FromStr};
+
+use serde::{Deserialize, Deserializer};
+use serde_with::{DeserializeFromStr, SerializeDisplay};
+
+/// Birth date as VK returns it in `bdate`: `D.M.YYYY`, or `D.M` when the year is hidden.
+///
+/// Serialized back into the same form. Stored in SQLite both as that string and as
+/// separate `bdate_day`, `bdate_month` and `bdate_year` columns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, SerializeDisplay, DeserializeFromStr)]
+pub struct BirthDate {
+    day: u8,
+    month: u8,
+    year: Option<u16>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BirthDateError {
+    pub input: String,
+    pub reason: &'static str,
+}
+
+impl fmt::Display for BirthDateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid birth date `{}`: {}", self.input, self.reason)
+    }
+}
+
+impl std::error::Error for BirthDateError {}
+
+fn is_leap(year: u16) -> bool {
+    (year.is_multiple_of(4) && !year.is_multiple_of(100)) || year.is_multiple_of(400)
+}
+
+/// Days in `month`, counting February 29 when the year is unknown.
+fn days_in_month(month: u8, year: Option<u16>) -> u8 {
+    match month {
+        2 if year.map(is_leap).unwrap_or(true) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+impl BirthDate {
+    pub fn new(day: u8, month: u8, year: Option<u16>) -> Result<Self, BirthDateError> {
+        let error = |reason| BirthDateError {
+            input: BirthDate { day, month, year }.to_string(),
+            reason,
+        };
+
+        if year == Some(0) {
+            return Err(error("year must be positive"));
+        }
+        if !(1..=12).contains(&month) {
+            return Err(error("month must be between 1 and 12"));
+        }
+        if day == 0 || day > days_in_month(month, year) {
+            return Err(error("no such day in this month"));
+        }
+        Ok(Self { day, month, year })
+    }
+
+    pub fn day(&self) -> u8 {
+        self.day
+    }
+
+    pub fn month(&self) -> u8 {
+        self.month
+    }
+
+    pub fn year(&self) -> Option<u16> {
+        self.year
+    }
+
+    /// Full years on the given date, or `None` when the year is hidden or the date
+    /// is before the birth.
+    pub fn age_on(&self, year: u16, month: u8, day: u8) -> Option<u16> {
+        let birth_year = self.year?;
+        let had_birthday = (month, day) >= (self.month, self.day);
+        let age = year.checked_sub(birth_year)?;
+
+        if had_birthday {
+            Some(age)
+        } else {
+            age.checked_sub(1)
+        }
+    }
+}
+
+impl fmt::Display for BirthDate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.day, self.month)?;
+        if let Some(year) = self.year {
+            write!(f, ".{}", year)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for BirthDate {
+    type Err = BirthDateError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = |reason| BirthDateError {
+            input: s.to_string(),
+            reason,
+        };
+
+        let parts: Vec<&str> = s.trim().split('.').collect();
+        if parts.len() != 2 && parts.len() != 3 {
+            return Err(error("expected D.M or D.M.YYYY"));
+        }
+
+        let day = parts[0].parse().map_err(|_| error("invalid day"))?;
+        let month = parts[1].parse().map_err(|_| error("invalid month"))?;
+        let year = match parts.get(2) {
+            Some(year) => Some(year.parse().map_err(|_| error("invalid year"))?),
+            None => None,
+        };
+
+        BirthDate::new(day, month, year).map_err(|e| error(e.reason))
+    }
+}
+
+/// Reads an optional `bdate`, treating an empty or invalid date like a missing one.
+///
+/// VK returns dates such as `31.2.1990`; failing on them would fail the whole
+/// `users.get` response they come in.
+pub(crate) fn invalid_as_none<'de, D>(deserializer: D) -> Result<Option<BirthDate>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(Option::<String>::deserialize(deserializer)?.and_then(|e| e.parse().ok()))
+}
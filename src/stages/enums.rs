@@ -0,0 +1,195 @@
+use rusqlite::types::{FromSql, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
+use serde::{Deserialize, Serialize};
+
+/// Integer codes of the VK API. Serialized and stored in SQLite as the code itself,
+/// codes without a variant are kept in `Unknown`.
+macro_rules! vk_enum {
+    ($(#[$meta:meta])* pub enum $name:ident {
+        $($(#[$variant_meta:meta])* $variant:ident = $code:literal),* $(,)?
+    }) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+        #[serde(from = "i64", into = "i64")]
+        pub enum $name {
+            $($(#[$variant_meta])* $variant,)*
+            Unknown(i64),
+        }
+
+        impl $name {
+            pub fn code(self) -> i64 {
+                match self {
+                    $($name::$variant => $code,)*
+                    $name::Unknown(code) => code,
+                }
+            }
+        }
+
+        impl From<i64> for $name {
+            fn from(code: i64) -> Self {
+                match code {
+                    $($code => $name::$variant,)*
+                    _ => $name::Unknown(code),
+                }
+            }
+        }
+
+        impl From<$name> for i64 {
+            fn from(value: $name) -> Self {
+                value.code()
+            }
+        }
+
+        impl ToSql for $name {
+            fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+                Ok(ToSqlOutput::from(self.code()))
+            }
+        }
+
+        impl FromSql for $name {
+            fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+                i64::column_result(value).map($name::from)
+            }
+        }
+    };
+}
+
+vk_enum! {
+    pub enum Sex {
+        NotSpecified = 0,
+        Female = 1,
+        Male = 2,
+    }
+}
+
+vk_enum! {
+    /// Relationship status.
+    pub enum Relation {
+        NotSpecified = 0,
+        Single = 1,
+        InRelationship = 2,
+        Engaged = 3,
+        Married = 4,
+        Complicated = 5,
+        ActivelySearching = 6,
+        InLove = 7,
+        CivilUnion = 8,
+    }
+}
+
+vk_enum! {
+    /// Client the user was last seen with.
+    pub enum Platform {
+        Mobile = 1,
+        Iphone = 2,
+        Ipad = 3,
+        Android = 4,
+        WindowsPhone = 5,
+        Windows = 6,
+        Web = 7,
+    }
+}
+
+vk_enum! {
+    /// `personal.political`.
+    pub enum Political {
+        Communist = 1,
+        Socialist = 2,
+        Moderate = 3,
+        Liberal = 4,
+        Conservative = 5,
+        Monarchist = 6,
+        UltraConservative = 7,
+        Apathetic = 8,
+        Libertarian = 9,
+    }
+}
+
+vk_enum! {
+    /// `personal.people_main`, what the user values most in others.
+    pub enum PeopleMain {
+        IntellectAndCreativity = 1,
+        KindnessAndHonesty = 2,
+        HealthAndBeauty = 3,
+        WealthAndPower = 4,
+        CourageAndPersistence = 5,
+        HumorAndLoveForLife = 6,
+    }
+}
+
+vk_enum! {
+    /// `personal.life_main`, what the user considers most important in life.
+    pub enum LifeMain {
+        FamilyAndChildren = 1,
+        CareerAndMoney = 2,
+        EntertainmentAndLeisure = 3,
+        ScienceAndResearch = 4,
+        ImprovingTheWorld = 5,
+        PersonalDevelopment = 6,
+        BeautyAndArt = 7,
+        FameAndInfluence = 8,
+    }
+}
+
+vk_enum! {
+    /// `personal.smoking` and `personal.alcohol`.
+    pub enum Attitude {
+        VeryNegative = 1,
+        Negative = 2,
+        Compromisable = 3,
+        Neutral = 4,
+        Positive = 5,
+    }
+}
+
+/// `occupation.type`, stored as the name VK uses.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(from = "String", into = "String")]
+pub enum OccupationType {
+    Work,
+    School,
+    University,
+    Unknown(String),
+}
+
+impl OccupationType {
+    pub fn as_str(&self) -> &str {
+        match self {
+            OccupationType::Work => "work",
+            OccupationType::School => "school",
+            OccupationType::University => "university",
+            OccupationType::Unknown(name) => name,
+        }
+    }
+}
+
+impl From<String> for OccupationType {
+    fn from(name: String) -> Self {
+        match name.as_str() {
+            "work" => OccupationType::Work,
+            "school" => OccupationType::School,
+            "university" => OccupationType::University,
+            _ => OccupationType::Unknown(name),
+        }
+    }
+}
+
+impl From<OccupationType> for String {
+    fn from(value: OccupationType) -> Self {
+        match value {
+            OccupationType::Unknown(name) => name,
+            known => known.as_str().to_string(),
+        }
+    }
+}
+
+impl ToSql for OccupationType {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.as_str()))
+    }
+}
+
+impl FromSql for OccupationType {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        String::column_result(value).map(OccupationType::from)
+    }
+}
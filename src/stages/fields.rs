@@ -0,0 +1,311 @@
+use std::{fmt, iter::FromIterator, str::FromStr};
+
+use crate::RobberError;
+
+macro_rules! user_fields {
+    ($($variant:ident = $name:literal),* $(,)?) => {
+        /// Optional profile field requested with the `fields` parameter of `users.get`.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+        pub enum UserField {
+            $($variant,)*
+        }
+
+        impl UserField {
+            pub const ALL: &'static [UserField] = &[$(UserField::$variant,)*];
+
+            /// Name VK expects in the `fields` parameter.
+            pub fn as_str(self) -> &'static str {
+                match self {
+                    $(UserField::$variant => $name,)*
+                }
+            }
+        }
+
+        impl FromStr for UserField {
+            type Err = RobberError;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                match s.trim() {
+                    $($name => Ok(UserField::$variant),)*
+                    other => Err(RobberError::FieldError {
+                        field: other.to_string(),
+                    }),
+                }
+            }
+        }
+    };
+}
+
+user_fields! {
+    About = "about",
+    Activities = "activities",
+    Bdate = "bdate",
+    Blacklisted = "blacklisted",
+    BlacklistedByMe = "blacklisted_by_me",
+    Books = "books",
+    CanPost = "can_post",
+    CanSeeAllPosts = "can_see_all_posts",
+    CanSeeAudio = "can_see_audio",
+    CanSendFriendRequest = "can_send_friend_request",
+    CanWritePrivateMessage = "can_write_private_message",
+    Career = "career",
+    City = "city",
+    CommonCount = "common_count",
+    Connections = "connections",
+    Contacts = "contacts",
+    Counters = "counters",
+    Country = "country",
+    CropPhoto = "crop_photo",
+    Domain = "domain",
+    Education = "education",
+    Exports = "exports",
+    FollowersCount = "followers_count",
+    FriendStatus = "friend_status",
+    Games = "games",
+    HasMobile = "has_mobile",
+    HasPhoto = "has_photo",
+    HomeTown = "home_town",
+    Interests = "interests",
+    IsFavorite = "is_favorite",
+    IsFriend = "is_friend",
+    IsHiddenFromFeed = "is_hidden_from_feed",
+    LastSeen = "last_seen",
+    Lists = "lists",
+    MaidenName = "maiden_name",
+    Military = "military",
+    Movies = "movies",
+    Music = "music",
+    Nickname = "nickname",
+    Occupation = "occupation",
+    Online = "online",
+    Personal = "personal",
+    Photo50 = "photo_50",
+    Photo100 = "photo_100",
+    Photo200 = "photo_200",
+    Photo200Orig = "photo_200_orig",
+    Photo400Orig = "photo_400_orig",
+    PhotoId = "photo_id",
+    PhotoMax = "photo_max",
+    PhotoMaxOrig = "photo_max_orig",
+    Quotes = "quotes",
+    Relation = "relation",
+    Relatives = "relatives",
+    Schools = "schools",
+    ScreenName = "screen_name",
+    Sex = "sex",
+    Site = "site",
+    Status = "status",
+    Timezone = "timezone",
+    Trending = "trending",
+    Tv = "tv",
+    Universities = "universities",
+    Verified = "verified",
+    WallDefault = "wall_default",
+}
+
+impl fmt::Display for UserField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Fields to request, rendered by `Display` into the VK `fields` parameter.
+///
+/// Parsing with `str::parse` rejects unknown names. Converting with `From<&str>`
+/// keeps them as they are and passes them through, like the string form did.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FieldSet {
+    fields: Vec<UserField>,
+    raw: Vec<String>,
+}
+
+impl FieldSet {
+    pub const PRESETS: &'static [&'static str] = &["basic", "education", "contacts", "all"];
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Names, sex, birth date, location, photo and activity.
+    pub fn basic() -> Self {
+        use UserField::*;
+
+        Self::from_iter(vec![
+            Sex,
+            Bdate,
+            City,
+            Country,
+            HomeTown,
+            Domain,
+            ScreenName,
+            Nickname,
+            MaidenName,
+            HasPhoto,
+            PhotoMaxOrig,
+            Verified,
+            Status,
+            LastSeen,
+            FollowersCount,
+            Relation,
+        ])
+    }
+
+    /// Schools, universities, jobs and military service.
+    pub fn education() -> Self {
+        use UserField::*;
+
+        Self::from_iter(vec![
+            Education,
+            Universities,
+            Schools,
+            Career,
+            Military,
+            Occupation,
+        ])
+    }
+
+    /// Phones, site and linked accounts.
+    pub fn contacts() -> Self {
+        use UserField::*;
+
+        Self::from_iter(vec![Contacts, HasMobile, Site, Connections])
+    }
+
+    pub fn all() -> Self {
+        Self::from_iter(UserField::ALL.iter().copied())
+    }
+
+    /// Preset called `name`, one of `FieldSet::PRESETS`.
+    pub fn preset(name: &str) -> Option<Self> {
+        match name {
+            "basic" => Some(Self::basic()),
+            "education" => Some(Self::education()),
+            "contacts" => Some(Self::contacts()),
+            "all" => Some(Self::all()),
+            _ => None,
+        }
+    }
+
+    /// Parses `fields` without checking names, unknown ones are sent as they are.
+    pub fn unchecked(fields: &str) -> Self {
+        let mut set = Self::new();
+        for name in fields.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            match name.parse() {
+                Ok(field) => set.insert(field),
+                Err(_) => {
+                    if !set.raw.iter().any(|e| e == name) {
+                        set.raw.push(name.to_string());
+                    }
+                }
+            }
+        }
+        set
+    }
+
+    pub fn with(mut self, field: UserField) -> Self {
+        self.insert(field);
+        self
+    }
+
+    pub fn insert(&mut self, field: UserField) {
+        if !self.contains(field) {
+            self.fields.push(field);
+        }
+    }
+
+    /// Adds every field of `other`.
+    pub fn extend(&mut self, other: FieldSet) {
+        other.fields.into_iter().for_each(|e| self.insert(e));
+        for name in other.raw {
+            if !self.raw.contains(&name) {
+                self.raw.push(name);
+            }
+        }
+    }
+
+    pub fn contains(&self, field: UserField) -> bool {
+        self.fields.contains(&field)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = UserField> + '_ {
+        self.fields.iter().copied()
+    }
+
+    /// Names that are not `UserField`s, kept by `FieldSet::unchecked`.
+    pub fn unknown(&self) -> &[String] {
+        &self.raw
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty() && self.raw.is_empty()
+    }
+}
+
+impl fmt::Display for FieldSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names = self
+            .fields
+            .iter()
+            .map(|e| e.as_str())
+            .chain(self.raw.iter().map(String::as_str));
+
+        for (i, name) in names.enumerate() {
+            if i > 0 {
+                f.write_str(",")?;
+            }
+            f.write_str(name)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for FieldSet {
+    type Err = RobberError;
+
+    /// Comma-separated field names, failing on the first unknown one.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(',')
+            .map(str::trim)
+            .filter(|e| !e.is_empty())
+            .map(str::parse)
+            .collect()
+    }
+}
+
+impl FromIterator<UserField> for FieldSet {
+    fn from_iter<T: IntoIterator<Item = UserField>>(iter: T) -> Self {
+        let mut set = Self::new();
+        iter.into_iter().for_each(|e| set.insert(e));
+        set
+    }
+}
+
+impl From<UserField> for FieldSet {
+    fn from(field: UserField) -> Self {
+        Self::new().with(field)
+    }
+}
+
+impl From<&str> for FieldSet {
+    fn from(fields: &str) -> Self {
+        Self::unchecked(fields)
+    }
+}
+
+impl From<String> for FieldSet {
+    fn from(fields: String) -> Self {
+        Self::unchecked(&fields)
+    }
+}
+
+impl From<&String> for FieldSet {
+    fn from(fields: &String) -> Self {
+        Self::unchecked(fields)
+    }
+}
+
+impl From<&FieldSet> for FieldSet {
+    fn from(fields: &FieldSet) -> Self {
+        fields.clone()
+    }
+}
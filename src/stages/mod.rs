@@ -1,2 +1,5 @@
+pub mod birth_date;
+pub mod enums;
+pub mod fields;
 pub mod groups;
 pub mod users;
@@ -1,17 +1,28 @@
-use crate::{
-    requests::api_manager::{ApiManager, API_TIMEOUT_MS},
-    RobberError,
+use crate::{history, migrations::unix_time, requests::api_manager::ApiManager, RobberError};
+
+use super::{
+    birth_date::{self, BirthDate},
+    enums::{Attitude, LifeMain, OccupationType, PeopleMain, Platform, Political, Relation, Sex},
+    fields::{FieldSet, UserField},
+};
+use itertools::Itertools;
+use rusqlite::{
+    params,
+    types::{ToSql, Type, ValueRef},
+    Connection, OptionalExtension, Row,
 };
-use rusqlite::params;
 use serde::{Deserialize, Serialize};
 use serde_with::serde_as;
+use std::{collections::VecDeque, time::Duration};
 
 use async_trait::async_trait;
 
 macro_rules! try_save {
     ($obj:expr, $name:ident, $conn:expr, $table_name:expr, $id:expr) => {{
-        if let Some(obj) = $obj {
-            obj.store($conn, $table_name, $id)
+        let user_id = $id;
+        if let Some(obj) = &$obj {
+            obj.store($conn, $table_name, user_id)
+                .map_err(|e| StoreError::locate(e, user_id, $table_name))
         } else {
             Ok(0)
         }
@@ -35,17 +46,183 @@ macro_rules! store_many {
     }};
 }
 
+/// Read accessor for a private field. Strings are borrowed as `&str`, nested values
+/// as references and everything else is copied. `name = field` renames the accessor.
+macro_rules! getter {
+    ($name:ident = $field:ident: Option<&str>) => {
+        pub fn $name(&self) -> Option<&str> {
+            self.$field.as_deref()
+        }
+    };
+    ($name:ident = $field:ident: &str) => {
+        pub fn $name(&self) -> &str {
+            &self.$field
+        }
+    };
+    ($name:ident = $field:ident: Option<&$ty:ty>) => {
+        pub fn $name(&self) -> Option<&$ty> {
+            self.$field.as_ref()
+        }
+    };
+    ($name:ident = $field:ident: &$ty:ty) => {
+        pub fn $name(&self) -> &$ty {
+            &self.$field
+        }
+    };
+    ($name:ident = $field:ident: $ty:ty) => {
+        pub fn $name(&self) -> $ty {
+            self.$field
+        }
+    };
+    ($field:ident: $($ty:tt)+) => {
+        getter!($field = $field: $($ty)+);
+    };
+}
+
+/// Chained setter of a nested value, for building users by hand; see `UserBuilder`.
+macro_rules! with {
+    ($name:ident = $field:ident: Option<$ty:ty>) => {
+        pub fn $name<T: Into<$ty>>(mut self, value: T) -> Self {
+            self.$field = Some(value.into());
+            self
+        }
+    };
+    ($name:ident = $field:ident: $ty:ty) => {
+        pub fn $name<T: Into<$ty>>(mut self, value: T) -> Self {
+            self.$field = value.into();
+            self
+        }
+    };
+}
+
+/// Setter of `UserBuilder` for an optional field of `User`.
+macro_rules! setter {
+    ($field:ident: $ty:ty) => {
+        pub fn $field<T: Into<$ty>>(mut self, value: T) -> Self {
+            self.user.$field = Some(value.into());
+            self
+        }
+    };
+}
+
+/// Failure to store one user, naming the table the failing row belongs to.
+#[derive(Debug)]
+pub struct StoreError {
+    pub user_id: i64,
+    pub table: String,
+    pub source: rusqlite::Error,
+}
+
+impl StoreError {
+    /// Attaches the user and table to a bare SQLite failure, leaving other errors as they are.
+    fn locate(error: RobberError, user_id: i64, table: &str) -> RobberError {
+        match error {
+            RobberError::SqliteError(source) => RobberError::StoreError(StoreError {
+                user_id,
+                table: table.to_string(),
+                source,
+            }),
+            e => e,
+        }
+    }
+}
+
+impl std::fmt::Display for StoreError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "failed to store user {} into {}: {}",
+            self.user_id, self.table, self.source
+        )
+    }
+}
+
+/// Like `RobberError`, the SQLite failure is part of the message and not returned as the source.
+impl std::error::Error for StoreError {}
+
 pub trait StoreExt {
     fn store(
-        self,
+        &self,
         connection: &rusqlite::Transaction,
         table_name: &str,
         user_id: i64,
-    ) -> Result<usize, rusqlite::Error>;
+    ) -> Result<usize, RobberError>;
+}
+
+/// Reads back what `StoreExt::store` wrote, one row per value.
+pub trait LoadExt: Sized {
+    /// Columns `from_row` expects, in order.
+    const COLUMNS: &'static str;
+
+    fn from_row(row: &Row) -> rusqlite::Result<Self>;
+}
+
+fn load_one<T: LoadExt>(
+    connection: &Connection,
+    table_name: &str,
+    user_id: i64,
+) -> rusqlite::Result<Option<T>> {
+    let query = format!(
+        "SELECT {} FROM {} WHERE user_id = ?",
+        T::COLUMNS,
+        table_name
+    );
+    connection
+        .prepare_cached(&query)?
+        .query_row(params![user_id], T::from_row)
+        .optional()
+}
+
+/// Rows of `table_name` for `user_id` in the order they were stored.
+fn load_rows<T: LoadExt>(
+    connection: &Connection,
+    table_name: &str,
+    user_id: i64,
+) -> rusqlite::Result<Vec<T>> {
+    let query = format!(
+        "SELECT {} FROM {} WHERE user_id = ? ORDER BY rowid",
+        T::COLUMNS,
+        table_name
+    );
+    let mut statement = connection.prepare_cached(&query)?;
+    let rows = statement.query_map(params![user_id], T::from_row)?;
+    rows.collect()
+}
+
+fn non_empty<T>(values: Vec<T>) -> Option<Vec<T>> {
+    if values.is_empty() {
+        None
+    } else {
+        Some(values)
+    }
+}
+
+/// Text column that SQLite may have stored as a number because of the column affinity.
+fn text(row: &Row, index: usize) -> rusqlite::Result<Option<String>> {
+    Ok(match row.get_raw(index) {
+        ValueRef::Null => None,
+        ValueRef::Integer(e) => Some(e.to_string()),
+        ValueRef::Real(e) => Some(e.to_string()),
+        ValueRef::Text(e) | ValueRef::Blob(e) => Some(String::from_utf8_lossy(e).into_owned()),
+    })
+}
+
+/// Integer column that SQLite may have stored as text because of the column affinity.
+fn integer(row: &Row, index: usize) -> rusqlite::Result<Option<i64>> {
+    match row.get_raw(index) {
+        ValueRef::Text(e) => std::str::from_utf8(e)
+            .ok()
+            .and_then(|e| e.trim().parse().ok())
+            .map(Some)
+            .ok_or_else(|| {
+                rusqlite::Error::InvalidColumnType(index, "integer".to_string(), Type::Text)
+            }),
+        _ => row.get(index),
+    }
 }
 
 #[serde_as]
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
 pub struct CareerInfo {
     group_id: Option<i64>,
 
@@ -66,52 +243,109 @@ pub struct CareerInfo {
     position: Option<String>,
 }
 
+impl CareerInfo {
+    with!(with_group_id = group_id: Option<i64>);
+    with!(with_company = company: Option<String>);
+    with!(with_country_id = country_id: Option<i64>);
+    with!(with_city_id = city_id: Option<i64>);
+    with!(with_city_name = city_name: Option<String>);
+    with!(with_from = from: Option<i64>);
+    with!(with_until = until: Option<i64>);
+    with!(with_position = position: Option<String>);
+    getter!(group_id: Option<i64>);
+    getter!(company: Option<&str>);
+    getter!(country_id: Option<i64>);
+    getter!(city_id: Option<i64>);
+    getter!(city_name: Option<&str>);
+    getter!(from: Option<i64>);
+    getter!(until: Option<i64>);
+    getter!(position: Option<&str>);
+}
+
 impl StoreExt for CareerInfo {
     fn store(
-        self,
+        &self,
         connection: &rusqlite::Transaction,
         table_name: &str,
         user_id: i64,
-    ) -> Result<usize, rusqlite::Error> {
+    ) -> Result<usize, RobberError> {
         let query = format!("INSERT OR REPLACE INTO {} (user_id, group_id, company, country_id, city_id, city_name, \"from\", \"until\", position) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)", table_name);
-        connection.execute(
-            &query,
-            params![
-                user_id,
-                self.group_id,
-                self.company,
-                self.country_id,
-                self.city_id,
-                self.city_name,
-                self.from,
-                self.until,
-                self.position
-            ],
-        )
+        connection
+            .execute(
+                &query,
+                params![
+                    user_id,
+                    self.group_id,
+                    self.company,
+                    self.country_id,
+                    self.city_id,
+                    self.city_name,
+                    self.from,
+                    self.until,
+                    self.position
+                ],
+            )
+            .map_err(RobberError::SqliteError)
+    }
+}
+
+impl LoadExt for CareerInfo {
+    const COLUMNS: &'static str =
+        "group_id, company, country_id, city_id, city_name, \"from\", \"until\", position";
+
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            group_id: row.get(0)?,
+            company: row.get(1)?,
+            country_id: row.get(2)?,
+            city_id: row.get(3)?,
+            city_name: row.get(4)?,
+            from: row.get(5)?,
+            until: row.get(6)?,
+            position: row.get(7)?,
+        })
     }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct City {
     id: i64,
 }
 
+impl City {
+    pub fn new(id: i64) -> Self {
+        Self { id }
+    }
+
+    getter!(id: i64);
+}
+
 impl StoreExt for City {
     fn store(
-        self,
+        &self,
         connection: &rusqlite::Transaction,
         table_name: &str,
         user_id: i64,
-    ) -> Result<usize, rusqlite::Error> {
+    ) -> Result<usize, RobberError> {
         let query = format!(
             "INSERT OR REPLACE INTO {} (user_id, id) VALUES (?, ?)",
             table_name
         );
-        connection.execute(&query, params![user_id, self.id])
+        connection
+            .execute(&query, params![user_id, self.id])
+            .map_err(RobberError::SqliteError)
     }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+impl LoadExt for City {
+    const COLUMNS: &'static str = "id";
+
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self { id: row.get(0)? })
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
 pub struct Counters {
     albums: i64,
     videos: i64,
@@ -125,55 +359,118 @@ pub struct Counters {
     pages: i64,
 }
 
+impl Counters {
+    with!(with_albums = albums: i64);
+    with!(with_videos = videos: i64);
+    with!(with_audios = audios: i64);
+    with!(with_photos = photos: i64);
+    with!(with_notes = notes: i64);
+    with!(with_friends = friends: i64);
+    with!(with_groups = groups: i64);
+    with!(with_user_videos = user_videos: i64);
+    with!(with_followers = followers: i64);
+    with!(with_pages = pages: i64);
+    getter!(albums: i64);
+    getter!(videos: i64);
+    getter!(audios: i64);
+    getter!(photos: i64);
+    getter!(notes: i64);
+    getter!(friends: i64);
+    getter!(groups: i64);
+    getter!(user_videos: i64);
+    getter!(followers: i64);
+    getter!(pages: i64);
+}
+
 impl StoreExt for Counters {
     fn store(
-        self,
+        &self,
         connection: &rusqlite::Transaction,
         table_name: &str,
         user_id: i64,
-    ) -> Result<usize, rusqlite::Error> {
+    ) -> Result<usize, RobberError> {
         let query = format!("INSERT OR REPLACE INTO {} (user_id, albums, videos, audios, photos, notes, friends, groups, user_videos, followers, pages) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)", table_name);
-        connection.execute(
-            &query,
-            params![
-                user_id,
-                self.albums,
-                self.videos,
-                self.audios,
-                self.photos,
-                self.notes,
-                self.friends,
-                self.groups,
-                self.user_videos,
-                self.followers,
-                self.pages
-            ],
-        )
+        connection
+            .execute(
+                &query,
+                params![
+                    user_id,
+                    self.albums,
+                    self.videos,
+                    self.audios,
+                    self.photos,
+                    self.notes,
+                    self.friends,
+                    self.groups,
+                    self.user_videos,
+                    self.followers,
+                    self.pages
+                ],
+            )
+            .map_err(RobberError::SqliteError)
     }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+impl LoadExt for Counters {
+    const COLUMNS: &'static str =
+        "albums, videos, audios, photos, notes, friends, groups, user_videos, followers, pages";
+
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            albums: row.get(0)?,
+            videos: row.get(1)?,
+            audios: row.get(2)?,
+            photos: row.get(3)?,
+            notes: row.get(4)?,
+            friends: row.get(5)?,
+            groups: row.get(6)?,
+            user_videos: row.get(7)?,
+            followers: row.get(8)?,
+            pages: row.get(9)?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct Country {
     id: i64,
 }
 
+impl Country {
+    pub fn new(id: i64) -> Self {
+        Self { id }
+    }
+
+    getter!(id: i64);
+}
+
 impl StoreExt for Country {
     fn store(
-        self,
+        &self,
         connection: &rusqlite::Transaction,
         table_name: &str,
         user_id: i64,
-    ) -> Result<usize, rusqlite::Error> {
+    ) -> Result<usize, RobberError> {
         let query = format!(
             "INSERT OR REPLACE INTO {} (user_id, id) VALUES (?, ?)",
             table_name
         );
-        connection.execute(&query, params![user_id, self.id])
+        connection
+            .execute(&query, params![user_id, self.id])
+            .map_err(RobberError::SqliteError)
+    }
+}
+
+impl LoadExt for Country {
+    const COLUMNS: &'static str = "id";
+
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self { id: row.get(0)? })
     }
 }
 
 #[serde_as]
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct EducationInfo {
     university: i64,
     university_name: String,
@@ -185,50 +482,113 @@ pub struct EducationInfo {
     graduation: Option<i64>,
 }
 
+impl EducationInfo {
+    pub fn new<T: Into<String>>(university: i64, university_name: T) -> Self {
+        Self {
+            university,
+            university_name: university_name.into(),
+            faculty: None,
+            faculty_name: None,
+            graduation: None,
+        }
+    }
+
+    with!(with_faculty = faculty: Option<i64>);
+    with!(with_faculty_name = faculty_name: Option<String>);
+    with!(with_graduation = graduation: Option<i64>);
+    getter!(university: i64);
+    getter!(university_name: &str);
+    getter!(faculty: Option<i64>);
+    getter!(faculty_name: Option<&str>);
+    getter!(graduation: Option<i64>);
+}
+
 impl StoreExt for EducationInfo {
     fn store(
-        self,
+        &self,
         connection: &rusqlite::Transaction,
         table_name: &str,
         user_id: i64,
-    ) -> Result<usize, rusqlite::Error> {
+    ) -> Result<usize, RobberError> {
         let query = format!("INSERT OR REPLACE INTO {} (user_id, university, university_name, faculty, faculty_name, graduation) VALUES (?, ?, ?, ?, ?, ?)", table_name);
-        connection.execute(
-            &query,
-            params![
-                user_id,
-                self.university,
-                self.university_name,
-                self.faculty,
-                self.faculty_name,
-                self.graduation
-            ],
-        )
+        connection
+            .execute(
+                &query,
+                params![
+                    user_id,
+                    self.university,
+                    self.university_name,
+                    self.faculty,
+                    self.faculty_name,
+                    self.graduation
+                ],
+            )
+            .map_err(RobberError::SqliteError)
     }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+impl LoadExt for EducationInfo {
+    const COLUMNS: &'static str = "university, university_name, faculty, faculty_name, graduation";
+
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            university: row.get(0)?,
+            university_name: row.get(1)?,
+            faculty: row.get(2)?,
+            faculty_name: row.get(3)?,
+            graduation: row.get(4)?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct LastSeen {
     time: i64,
-    platform: Option<i64>,
+    platform: Option<Platform>,
+}
+
+impl LastSeen {
+    pub fn new(time: i64) -> Self {
+        Self {
+            time,
+            platform: None,
+        }
+    }
+
+    with!(with_platform = platform: Option<Platform>);
+    getter!(time: i64);
+    getter!(platform: Option<Platform>);
 }
 
 impl StoreExt for LastSeen {
     fn store(
-        self,
+        &self,
         connection: &rusqlite::Transaction,
         table_name: &str,
         user_id: i64,
-    ) -> Result<usize, rusqlite::Error> {
+    ) -> Result<usize, RobberError> {
         let query = format!(
             "INSERT OR REPLACE INTO {} (user_id, time, platform) VALUES (?, ?, ?)",
             table_name
         );
-        connection.execute(&query, params![user_id, self.time, self.platform])
+        connection
+            .execute(&query, params![user_id, self.time, self.platform])
+            .map_err(RobberError::SqliteError)
+    }
+}
+
+impl LoadExt for LastSeen {
+    const COLUMNS: &'static str = "time, platform";
+
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            time: row.get(0)?,
+            platform: row.get(1)?,
+        })
     }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct MilitaryInfo {
     unit: String,
     unit_id: i64,
@@ -237,55 +597,121 @@ pub struct MilitaryInfo {
     until: Option<i64>,
 }
 
+impl MilitaryInfo {
+    pub fn new<T: Into<String>>(unit: T, unit_id: i64, country_id: i64) -> Self {
+        Self {
+            unit: unit.into(),
+            unit_id,
+            country_id,
+            from: None,
+            until: None,
+        }
+    }
+
+    with!(with_from = from: Option<i64>);
+    with!(with_until = until: Option<i64>);
+    getter!(unit: &str);
+    getter!(unit_id: i64);
+    getter!(country_id: i64);
+    getter!(from: Option<i64>);
+    getter!(until: Option<i64>);
+}
+
 impl StoreExt for MilitaryInfo {
     fn store(
-        self,
+        &self,
         connection: &rusqlite::Transaction,
         table_name: &str,
         user_id: i64,
-    ) -> Result<usize, rusqlite::Error> {
+    ) -> Result<usize, RobberError> {
         let query = format!("INSERT OR REPLACE INTO {} (user_id, unit, unit_id, country_id, \"from\", \"until\") VALUES (?, ?, ?, ?, ?, ?)", table_name);
-        connection.execute(
-            &query,
-            params![
-                user_id,
-                self.unit,
-                self.unit_id,
-                self.country_id,
-                self.from,
-                self.until
-            ],
-        )
+        connection
+            .execute(
+                &query,
+                params![
+                    user_id,
+                    self.unit,
+                    self.unit_id,
+                    self.country_id,
+                    self.from,
+                    self.until
+                ],
+            )
+            .map_err(RobberError::SqliteError)
     }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+impl LoadExt for MilitaryInfo {
+    const COLUMNS: &'static str = "unit, unit_id, country_id, \"from\", \"until\"";
+
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            unit: row.get(0)?,
+            unit_id: row.get(1)?,
+            country_id: row.get(2)?,
+            from: row.get(3)?,
+            until: row.get(4)?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct Occupation {
     #[serde(rename = "type")]
-    r#type: String,
+    r#type: OccupationType,
     id: Option<i64>,
     name: Option<String>,
 }
 
+impl Occupation {
+    pub fn new(kind: OccupationType) -> Self {
+        Self {
+            r#type: kind,
+            id: None,
+            name: None,
+        }
+    }
+
+    with!(with_id = id: Option<i64>);
+    with!(with_name = name: Option<String>);
+    getter!(kind = r#type: &OccupationType);
+    getter!(id: Option<i64>);
+    getter!(name: Option<&str>);
+}
+
 impl StoreExt for Occupation {
     fn store(
-        self,
+        &self,
         connection: &rusqlite::Transaction,
         table_name: &str,
         user_id: i64,
-    ) -> Result<usize, rusqlite::Error> {
+    ) -> Result<usize, RobberError> {
         let query = format!(
             "INSERT OR REPLACE INTO {} (user_id, type, id, name) VALUES (?, ?, ?, ?)",
             table_name
         );
-        connection.execute(&query, params![user_id, self.r#type, self.id, self.name])
+        connection
+            .execute(&query, params![user_id, self.r#type, self.id, self.name])
+            .map_err(RobberError::SqliteError)
+    }
+}
+
+impl LoadExt for Occupation {
+    const COLUMNS: &'static str = "type, id, name";
+
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            r#type: row.get(0)?,
+            id: row.get(1)?,
+            name: row.get(2)?,
+        })
     }
 }
 
 #[serde_as]
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
 pub struct PersonalInfo {
-    political: Option<i64>,
+    political: Option<Political>,
     langs: Option<Vec<String>>,
 
     #[serde_as(as = "serde_with::NoneAsEmptyString")]
@@ -295,53 +721,118 @@ pub struct PersonalInfo {
     #[serde_as(as = "serde_with::NoneAsEmptyString")]
     #[serde(default)]
     inspired_by: Option<String>,
-    people_main: Option<i64>,
-    life_main: Option<i64>,
-    smoking: Option<i64>,
-    alcohol: Option<i64>,
+    people_main: Option<PeopleMain>,
+    life_main: Option<LifeMain>,
+    smoking: Option<Attitude>,
+    alcohol: Option<Attitude>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+impl PersonalInfo {
+    with!(with_political = political: Option<Political>);
+    with!(with_langs = langs: Option<Vec<String>>);
+    with!(with_religion = religion: Option<String>);
+    with!(with_inspired_by = inspired_by: Option<String>);
+    with!(with_people_main = people_main: Option<PeopleMain>);
+    with!(with_life_main = life_main: Option<LifeMain>);
+    with!(with_smoking = smoking: Option<Attitude>);
+    with!(with_alcohol = alcohol: Option<Attitude>);
+    getter!(political: Option<Political>);
+    getter!(religion: Option<&str>);
+    getter!(inspired_by: Option<&str>);
+    getter!(people_main: Option<PeopleMain>);
+    getter!(life_main: Option<LifeMain>);
+    getter!(smoking: Option<Attitude>);
+    getter!(alcohol: Option<Attitude>);
+
+    pub fn langs(&self) -> &[String] {
+        self.langs.as_deref().unwrap_or(&[])
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 #[serde(untagged)]
 pub enum Personal {
     Value(PersonalInfo),
     None(Vec<()>),
 }
 
+impl Personal {
+    /// `None` when VK sent an empty array instead of the object.
+    pub fn info(&self) -> Option<&PersonalInfo> {
+        match self {
+            Personal::Value(e) => Some(e),
+            Personal::None(_) => None,
+        }
+    }
+}
+
+impl From<PersonalInfo> for Personal {
+    fn from(value: PersonalInfo) -> Self {
+        Personal::Value(value)
+    }
+}
+
 impl StoreExt for Personal {
     fn store(
-        self,
+        &self,
         connection: &rusqlite::Transaction,
         table_name: &str,
         user_id: i64,
-    ) -> Result<usize, rusqlite::Error> {
+    ) -> Result<usize, RobberError> {
         match self {
             Personal::Value(value) => {
                 let query = format!("INSERT OR REPLACE INTO {} (user_id, political, langs, religion, inspired_by, people_main, life_main, smoking, alcohol) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)", table_name);
-                let langs = value.langs.map(|e| e.join(", "));
-
-                connection.execute(
-                    &query,
-                    params![
-                        user_id,
-                        value.political,
-                        langs,
-                        value.religion,
-                        value.inspired_by,
-                        value.people_main,
-                        value.life_main,
-                        value.smoking,
-                        value.alcohol
-                    ],
-                )
+                let langs = value.langs.as_ref().map(|e| e.join(", "));
+
+                connection
+                    .execute(
+                        &query,
+                        params![
+                            user_id,
+                            value.political,
+                            langs,
+                            value.religion,
+                            value.inspired_by,
+                            value.people_main,
+                            value.life_main,
+                            value.smoking,
+                            value.alcohol
+                        ],
+                    )
+                    .map_err(RobberError::SqliteError)
             }
             Personal::None(_) => Ok(0),
         }
     }
 }
 
+impl LoadExt for PersonalInfo {
+    const COLUMNS: &'static str =
+        "political, langs, religion, inspired_by, people_main, life_main, smoking, alcohol";
+
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        let langs: Option<String> = row.get(1)?;
+
+        Ok(Self {
+            political: row.get(0)?,
+            langs: langs.map(|e| {
+                e.split(", ")
+                    .filter(|e| !e.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            }),
+            religion: row.get(2)?,
+            inspired_by: row.get(3)?,
+            people_main: row.get(4)?,
+            life_main: row.get(5)?,
+            smoking: row.get(6)?,
+            alcohol: row.get(7)?,
+        })
+    }
+}
+
 #[serde_as]
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct Relative {
     id: Option<i64>,
 
@@ -352,35 +843,80 @@ pub struct Relative {
     r#type: String,
 }
 
+impl Relative {
+    /// Relative of the given kind, e.g. `sibling` or `parent`.
+    pub fn new<T: Into<String>>(kind: T) -> Self {
+        Self {
+            id: None,
+            name: None,
+            r#type: kind.into(),
+        }
+    }
+
+    with!(with_id = id: Option<i64>);
+    with!(with_name = name: Option<String>);
+    getter!(id: Option<i64>);
+    getter!(name: Option<&str>);
+    getter!(kind = r#type: &str);
+}
+
 impl StoreExt for Relative {
     fn store(
-        self,
+        &self,
         connection: &rusqlite::Transaction,
         table_name: &str,
         user_id: i64,
-    ) -> Result<usize, rusqlite::Error> {
+    ) -> Result<usize, RobberError> {
         let query = format!(
             "INSERT OR REPLACE INTO {} (user_id, id, name, type) VALUES (?, ?, ?, ?)",
             table_name
         );
 
-        connection.execute(&query, params![user_id, self.id, self.name, self.r#type])
+        connection
+            .execute(&query, params![user_id, self.id, self.name, self.r#type])
+            .map_err(RobberError::SqliteError)
     }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+impl LoadExt for Relative {
+    const COLUMNS: &'static str = "id, name, type";
+
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            r#type: row.get(2)?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 #[serde(untagged)]
 pub enum Relatives {
     Value(Vec<Relative>),
 }
 
+impl Relatives {
+    pub fn as_slice(&self) -> &[Relative] {
+        match self {
+            Relatives::Value(e) => e,
+        }
+    }
+}
+
+impl From<Vec<Relative>> for Relatives {
+    fn from(values: Vec<Relative>) -> Self {
+        Relatives::Value(values)
+    }
+}
+
 impl StoreExt for Relatives {
     fn store(
-        self,
+        &self,
         connection: &rusqlite::Transaction,
         table_name: &str,
         user_id: i64,
-    ) -> Result<usize, rusqlite::Error> {
+    ) -> Result<usize, RobberError> {
         match self {
             Relatives::Value(e) => {
                 let mut total_length: usize = 0;
@@ -400,34 +936,66 @@ impl StoreExt for Relatives {
     }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct RelationPartner {
     id: i64,
     first_name: String,
     last_name: String,
 }
 
+impl RelationPartner {
+    pub fn new<T1, T2>(id: i64, first_name: T1, last_name: T2) -> Self
+    where
+        T1: Into<String>,
+        T2: Into<String>,
+    {
+        Self {
+            id,
+            first_name: first_name.into(),
+            last_name: last_name.into(),
+        }
+    }
+
+    getter!(id: i64);
+    getter!(first_name: &str);
+    getter!(last_name: &str);
+}
+
 impl StoreExt for RelationPartner {
     fn store(
-        self,
+        &self,
         connection: &rusqlite::Transaction,
         table_name: &str,
         user_id: i64,
-    ) -> Result<usize, rusqlite::Error> {
+    ) -> Result<usize, RobberError> {
         let query = format!(
             "INSERT OR REPLACE INTO {} (user_id, id, first_name, last_name) VALUES (?, ?, ?, ?)",
             table_name
         );
 
-        connection.execute(
-            &query,
-            params![user_id, self.id, self.first_name, self.last_name],
-        )
+        connection
+            .execute(
+                &query,
+                params![user_id, self.id, self.first_name, self.last_name],
+            )
+            .map_err(RobberError::SqliteError)
+    }
+}
+
+impl LoadExt for RelationPartner {
+    const COLUMNS: &'static str = "id, first_name, last_name";
+
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            id: row.get(0)?,
+            first_name: row.get(1)?,
+            last_name: row.get(2)?,
+        })
     }
 }
 
 #[serde_as]
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct School {
     #[serde_as(as = "serde_with::NoneAsEmptyString")]
     #[serde(default)]
@@ -453,36 +1021,94 @@ pub struct School {
     r#type: Option<i64>,
 }
 
+impl School {
+    pub fn new(country: i64, city: i64) -> Self {
+        Self {
+            id: None,
+            country,
+            city,
+            name: None,
+            year_from: None,
+            year_to: None,
+            year_graduated: None,
+            class: None,
+            speciality: None,
+            r#type: None,
+        }
+    }
+
+    with!(with_id = id: Option<String>);
+    with!(with_name = name: Option<String>);
+    with!(with_year_from = year_from: Option<i64>);
+    with!(with_year_to = year_to: Option<i64>);
+    with!(with_year_graduated = year_graduated: Option<i64>);
+    with!(with_class = class: Option<String>);
+    with!(with_speciality = speciality: Option<String>);
+    with!(with_kind = r#type: Option<i64>);
+    getter!(id: Option<&str>);
+    getter!(country: i64);
+    getter!(city: i64);
+    getter!(name: Option<&str>);
+    getter!(year_from: Option<i64>);
+    getter!(year_to: Option<i64>);
+    getter!(year_graduated: Option<i64>);
+    getter!(class: Option<&str>);
+    getter!(speciality: Option<&str>);
+    getter!(kind = r#type: Option<i64>);
+}
+
 impl StoreExt for School {
     fn store(
-        self,
+        &self,
         connection: &rusqlite::Transaction,
         table_name: &str,
         user_id: i64,
-    ) -> Result<usize, rusqlite::Error> {
+    ) -> Result<usize, RobberError> {
         let query = format!("INSERT OR REPLACE INTO {} (user_id, id, country, city, name, \"year_from\", year_to, year_graduated, class, speciality, type) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)", table_name);
 
-        connection.execute(
-            &query,
-            params![
-                user_id,
-                self.id,
-                self.country,
-                self.city,
-                self.name,
-                self.year_from,
-                self.year_to,
-                self.year_graduated,
-                self.class,
-                self.speciality,
-                self.r#type
-            ],
-        )
+        connection
+            .execute(
+                &query,
+                params![
+                    user_id,
+                    self.id,
+                    self.country,
+                    self.city,
+                    self.name,
+                    self.year_from,
+                    self.year_to,
+                    self.year_graduated,
+                    self.class,
+                    self.speciality,
+                    self.r#type
+                ],
+            )
+            .map_err(RobberError::SqliteError)
+    }
+}
+
+impl LoadExt for School {
+    const COLUMNS: &'static str =
+        "id, country, city, name, \"year_from\", year_to, year_graduated, class, speciality, type";
+
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            id: text(row, 0)?,
+            country: row.get(1)?,
+            city: row.get(2)?,
+            name: row.get(3)?,
+            year_from: row.get(4)?,
+            year_to: row.get(5)?,
+            year_graduated: row.get(6)?,
+            class: row.get(7)?,
+            speciality: row.get(8)?,
+            r#type: row.get(9)?,
+        })
     }
 }
 
 #[serde_as]
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
 pub struct Contacts {
     #[serde_as(as = "serde_with::NoneAsEmptyString")]
     #[serde(default)]
@@ -492,28 +1118,48 @@ pub struct Contacts {
     home_phone: Option<String>,
 }
 
+impl Contacts {
+    with!(with_mobile_phone = mobile_phone: Option<String>);
+    with!(with_home_phone = home_phone: Option<String>);
+    getter!(mobile_phone: Option<&str>);
+    getter!(home_phone: Option<&str>);
+}
+
 impl StoreExt for Contacts {
     fn store(
-        self,
+        &self,
         connection: &rusqlite::Transaction,
         table_name: &str,
         user_id: i64,
-    ) -> Result<usize, rusqlite::Error> {
+    ) -> Result<usize, RobberError> {
         if self.mobile_phone.is_some() || self.home_phone.is_some() {
             let query = format!(
                 "INSERT OR REPLACE INTO {} (user_id, mobile_phone, home_phone) VALUES (?, ?, ?)",
                 table_name
             );
 
-            connection.execute(&query, params![user_id, self.mobile_phone, self.home_phone])
+            connection
+                .execute(&query, params![user_id, self.mobile_phone, self.home_phone])
+                .map_err(RobberError::SqliteError)
         } else {
             Ok(0)
         }
     }
 }
 
+impl LoadExt for Contacts {
+    const COLUMNS: &'static str = "mobile_phone, home_phone";
+
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            mobile_phone: row.get(0)?,
+            home_phone: row.get(1)?,
+        })
+    }
+}
+
 #[serde_as]
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct University {
     id: i64,
     country: i64,
@@ -540,49 +1186,123 @@ pub struct University {
     education_status: Option<String>,
 }
 
+impl University {
+    pub fn new<T: Into<String>>(id: i64, country: i64, city: i64, name: T) -> Self {
+        Self {
+            id,
+            country,
+            city,
+            name: name.into(),
+            faculty: None,
+            faculty_name: None,
+            chair: None,
+            chair_name: None,
+            graduation: None,
+            education_form: None,
+            education_status: None,
+        }
+    }
+
+    with!(with_faculty = faculty: Option<i64>);
+    with!(with_faculty_name = faculty_name: Option<String>);
+    with!(with_chair = chair: Option<i64>);
+    with!(with_chair_name = chair_name: Option<String>);
+    with!(with_graduation = graduation: Option<i64>);
+    with!(with_education_form = education_form: Option<String>);
+    with!(with_education_status = education_status: Option<String>);
+    getter!(id: i64);
+    getter!(country: i64);
+    getter!(city: i64);
+    getter!(name: &str);
+    getter!(faculty: Option<i64>);
+    getter!(faculty_name: Option<&str>);
+    getter!(chair: Option<i64>);
+    getter!(chair_name: Option<&str>);
+    getter!(graduation: Option<i64>);
+    getter!(education_form: Option<&str>);
+    getter!(education_status: Option<&str>);
+}
+
 impl StoreExt for University {
     fn store(
-        self,
+        &self,
         connection: &rusqlite::Transaction,
         table_name: &str,
         user_id: i64,
-    ) -> Result<usize, rusqlite::Error> {
+    ) -> Result<usize, RobberError> {
         let query = format!("INSERT OR REPLACE INTO {} (user_id, id, country, city, name, faculty, faculty_name, chair, chair_name, graduation, education_form, education_status) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)", table_name);
 
-        connection.execute(
-            &query,
-            params![
-                user_id,
-                self.id,
-                self.country,
-                self.city,
-                self.name,
-                self.faculty,
-                self.faculty_name,
-                self.chair,
-                self.chair_name,
-                self.graduation,
-                self.education_form,
-                self.education_status
-            ],
-        )
+        connection
+            .execute(
+                &query,
+                params![
+                    user_id,
+                    self.id,
+                    self.country,
+                    self.city,
+                    self.name,
+                    self.faculty,
+                    self.faculty_name,
+                    self.chair,
+                    self.chair_name,
+                    self.graduation,
+                    self.education_form,
+                    self.education_status
+                ],
+            )
+            .map_err(RobberError::SqliteError)
     }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+impl LoadExt for University {
+    const COLUMNS: &'static str = "id, country, city, name, faculty, faculty_name, chair, chair_name, graduation, education_form, education_status";
+
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            id: row.get(0)?,
+            country: row.get(1)?,
+            city: row.get(2)?,
+            name: row.get(3)?,
+            faculty: row.get(4)?,
+            faculty_name: row.get(5)?,
+            chair: row.get(6)?,
+            chair_name: row.get(7)?,
+            graduation: row.get(8)?,
+            education_form: row.get(9)?,
+            education_status: row.get(10)?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 #[serde(untagged)]
 pub enum Career {
     One(CareerInfo),
     Many(Vec<CareerInfo>),
 }
 
+impl Career {
+    pub fn as_slice(&self) -> &[CareerInfo] {
+        match self {
+            Career::One(e) => std::slice::from_ref(e),
+            Career::Many(e) => e,
+        }
+    }
+}
+
+impl From<Vec<CareerInfo>> for Career {
+    fn from(values: Vec<CareerInfo>) -> Self {
+        Career::Many(values)
+    }
+}
+
 impl StoreExt for Career {
     fn store(
-        self,
+        &self,
         connection: &rusqlite::Transaction,
         table_name: &str,
         user_id: i64,
-    ) -> Result<usize, rusqlite::Error> {
+    ) -> Result<usize, RobberError> {
         match self {
             Career::One(e) => e.store(connection, table_name, user_id),
             Career::Many(e) => store_many!(e, connection, table_name, user_id),
@@ -590,58 +1310,101 @@ impl StoreExt for Career {
     }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 #[serde(untagged)]
 pub enum Universities {
     Value(Vec<University>),
 }
 
+impl Universities {
+    pub fn as_slice(&self) -> &[University] {
+        match self {
+            Universities::Value(e) => e,
+        }
+    }
+}
+
+impl From<Vec<University>> for Universities {
+    fn from(values: Vec<University>) -> Self {
+        Universities::Value(values)
+    }
+}
+
 impl StoreExt for Universities {
     fn store(
-        self,
+        &self,
         connection: &rusqlite::Transaction,
         table_name: &str,
         user_id: i64,
-    ) -> Result<usize, rusqlite::Error> {
+    ) -> Result<usize, RobberError> {
         match self {
             Universities::Value(e) => store_many!(e, connection, table_name, user_id),
         }
     }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 #[serde(untagged)]
 pub enum Schools {
     Value(Vec<School>),
 }
 
+impl Schools {
+    pub fn as_slice(&self) -> &[School] {
+        match self {
+            Schools::Value(e) => e,
+        }
+    }
+}
+
+impl From<Vec<School>> for Schools {
+    fn from(values: Vec<School>) -> Self {
+        Schools::Value(values)
+    }
+}
+
 impl StoreExt for Schools {
     fn store(
-        self,
+        &self,
         connection: &rusqlite::Transaction,
         table_name: &str,
         user_id: i64,
-    ) -> Result<usize, rusqlite::Error> {
+    ) -> Result<usize, RobberError> {
         match self {
             Schools::Value(e) => store_many!(e, connection, table_name, user_id),
         }
     }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 #[serde(untagged)]
 pub enum Military {
     One(MilitaryInfo),
     Many(Vec<MilitaryInfo>),
 }
 
+impl Military {
+    pub fn as_slice(&self) -> &[MilitaryInfo] {
+        match self {
+            Military::One(e) => std::slice::from_ref(e),
+            Military::Many(e) => e,
+        }
+    }
+}
+
+impl From<Vec<MilitaryInfo>> for Military {
+    fn from(values: Vec<MilitaryInfo>) -> Self {
+        Military::Many(values)
+    }
+}
+
 impl StoreExt for Military {
     fn store(
-        self,
+        &self,
         connection: &rusqlite::Transaction,
         table_name: &str,
         user_id: i64,
-    ) -> Result<usize, rusqlite::Error> {
+    ) -> Result<usize, RobberError> {
         match self {
             Military::One(e) => e.store(connection, table_name, user_id),
             Military::Many(e) => store_many!(e, connection, table_name, user_id),
@@ -650,7 +1413,7 @@ impl StoreExt for Military {
 }
 
 #[serde_as]
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
 pub struct User {
     id: i64,
 
@@ -670,9 +1433,8 @@ pub struct User {
     #[serde(default)]
     activities: Option<String>,
 
-    #[serde_as(as = "serde_with::NoneAsEmptyString")]
-    #[serde(default)]
-    bdate: Option<String>,
+    #[serde(default, deserialize_with = "birth_date::invalid_as_none")]
+    bdate: Option<BirthDate>,
 
     #[serde_as(as = "serde_with::NoneAsEmptyString")]
     #[serde(default)]
@@ -756,14 +1518,14 @@ pub struct User {
     quotes: Option<String>,
     relatives: Option<Relatives>,
 
-    relation: Option<i64>,
+    relation: Option<Relation>,
     relation_partner: Option<RelationPartner>,
     schools: Option<Schools>,
 
     #[serde_as(as = "serde_with::NoneAsEmptyString")]
     #[serde(default)]
     screen_name: Option<String>,
-    sex: Option<i64>,
+    sex: Option<Sex>,
 
     #[serde_as(as = "serde_with::NoneAsEmptyString")]
     #[serde(default)]
@@ -778,56 +1540,389 @@ pub struct User {
     tv: Option<String>,
     universities: Option<Universities>,
     verified: Option<i64>,
+
+    /// Unix time the user was fetched at, set by `store` for users from the API.
+    #[serde(skip)]
+    fetched_at: Option<i64>,
+
+    /// Fields the user was fetched with, set for users from `users.get`.
+    #[serde(skip)]
+    fetched_fields: Option<FieldSet>,
 }
 
 impl User {
+    /// Builder for users that did not come from the API, mostly for tests.
+    pub fn builder<F: Into<String>, L: Into<String>>(
+        id: i64,
+        first_name: F,
+        last_name: L,
+    ) -> UserBuilder {
+        UserBuilder {
+            user: User {
+                id,
+                first_name: first_name.into(),
+                last_name: last_name.into(),
+                ..Default::default()
+            },
+        }
+    }
+
+    getter!(id: i64);
+    getter!(first_name: &str);
+    getter!(last_name: &str);
+    getter!(deactivated: Option<&str>);
+    getter!(is_closed: Option<bool>);
+    getter!(about: Option<&str>);
+    getter!(activities: Option<&str>);
+    getter!(bdate: Option<BirthDate>);
+    getter!(books: Option<&str>);
+    getter!(city: Option<&City>);
+    getter!(skype: Option<&str>);
+    getter!(facebook: Option<&str>);
+    getter!(twitter: Option<&str>);
+    getter!(livejournal: Option<&str>);
+    getter!(instagram: Option<&str>);
+    getter!(contacts: Option<&Contacts>);
+    getter!(counters: Option<&Counters>);
+    getter!(country: Option<&Country>);
+    getter!(domain: Option<&str>);
+    getter!(education: Option<&EducationInfo>);
+    getter!(followers_count: Option<i64>);
+    getter!(games: Option<&str>);
+    getter!(has_mobile: Option<i64>);
+    getter!(has_photo: Option<i64>);
+    getter!(home_town: Option<&str>);
+    getter!(interests: Option<&str>);
+    getter!(last_seen: Option<&LastSeen>);
+    getter!(maiden_name: Option<&str>);
+    getter!(movies: Option<&str>);
+    getter!(music: Option<&str>);
+    getter!(nickname: Option<&str>);
+    getter!(occupation: Option<&Occupation>);
+    getter!(photo_max_orig: Option<&str>);
+    getter!(quotes: Option<&str>);
+    getter!(relation: Option<Relation>);
+    getter!(relation_partner: Option<&RelationPartner>);
+    getter!(screen_name: Option<&str>);
+    getter!(sex: Option<Sex>);
+    getter!(site: Option<&str>);
+    getter!(status: Option<&str>);
+    getter!(tv: Option<&str>);
+    getter!(verified: Option<i64>);
+    getter!(fetched_at: Option<i64>);
+    getter!(fetched_fields: Option<&FieldSet>);
+
+    /// Whether `field` came with this user: it was requested, or, when the fields the
+    /// user was fetched with are unknown, `present` says it has a value.
+    pub(crate) fn was_fetched(&self, field: UserField, present: bool) -> bool {
+        match &self.fetched_fields {
+            Some(fields) => fields.contains(field),
+            None => present,
+        }
+    }
+
+    pub fn career(&self) -> &[CareerInfo] {
+        self.career.as_ref().map(Career::as_slice).unwrap_or(&[])
+    }
+
+    pub fn military(&self) -> &[MilitaryInfo] {
+        self.military
+            .as_ref()
+            .map(Military::as_slice)
+            .unwrap_or(&[])
+    }
+
+    pub fn universities(&self) -> &[University] {
+        self.universities
+            .as_ref()
+            .map(Universities::as_slice)
+            .unwrap_or(&[])
+    }
+
+    pub fn schools(&self) -> &[School] {
+        self.schools.as_ref().map(Schools::as_slice).unwrap_or(&[])
+    }
+
+    pub fn relatives(&self) -> &[Relative] {
+        self.relatives
+            .as_ref()
+            .map(Relatives::as_slice)
+            .unwrap_or(&[])
+    }
+
+    pub fn personal(&self) -> Option<&PersonalInfo> {
+        self.personal.as_ref().and_then(Personal::info)
+    }
+
     pub fn store(
-        self,
+        &self,
         connection: &rusqlite::Transaction,
         table_name: &str,
-    ) -> Result<(), rusqlite::Error> {
-        let query = format!("INSERT OR REPLACE INTO {} (id, first_name, last_name, deactivated, is_closed, about, activities, bdate, books, domain, followers_count, games, has_mobile, has_photo, home_town, interests, maiden_name, movies, music, nickname, photo_max_orig, quotes, screen_name, sex, site, status, tv, verified, skype, facebook, twitter, livejournal, instagram, relation) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)", table_name);
-
-        if let Err(e) = connection.execute(
-            &query,
-            params![
-                self.id,
-                self.first_name,
-                self.last_name,
-                self.deactivated,
-                self.is_closed,
-                self.about,
-                self.activities,
-                self.bdate,
-                self.books,
-                self.domain,
-                self.followers_count,
-                self.games,
-                self.has_mobile,
-                self.has_photo,
-                self.home_town,
-                self.interests,
-                self.maiden_name,
-                self.movies,
-                self.music,
-                self.nickname,
-                self.photo_max_orig,
-                self.quotes,
-                self.screen_name,
-                self.sex,
-                self.site,
-                self.status,
-                self.tv,
-                self.verified,
-                self.skype,
-                self.facebook,
-                self.twitter,
-                self.livejournal,
-                self.instagram,
-                self.relation
-            ],
-        ) {
-            panic!("Failed saving object: {}", e);
+    ) -> Result<(), RobberError> {
+        let history = history::has_history(connection).map_err(|source| {
+            RobberError::StoreError(StoreError {
+                user_id: self.id,
+                table: history::HISTORY_TABLE.to_string(),
+                source,
+            })
+        })?;
+        self.store_with_history(connection, table_name, history)
+    }
+
+    /// Same as `store`, for callers that looked up once whether history is enabled.
+    pub(crate) fn store_with_history(
+        &self,
+        connection: &rusqlite::Transaction,
+        table_name: &str,
+        history: bool,
+    ) -> Result<(), RobberError> {
+        let fetched_at = self.fetched_at.unwrap_or_else(unix_time);
+        if history {
+            if let Err(source) = history::record(connection, self, fetched_at) {
+                return Err(RobberError::StoreError(StoreError {
+                    user_id: self.id,
+                    table: history::HISTORY_TABLE.to_string(),
+                    source,
+                }));
+            }
+        }
+
+        // Columns of a field that was not fetched this time stay as they were, like the
+        // rows of the nested tables below. `fetched_at` is when the row was last fetched.
+        let bdate = self.bdate.map(|e| e.to_string());
+        let bdate_day = self.bdate.map(|e| e.day());
+        let bdate_month = self.bdate.map(|e| e.month());
+        let bdate_year = self.bdate.and_then(|e| e.year());
+        let optional: &[(&str, UserField, bool, &dyn ToSql)] = &[
+            ("about", UserField::About, self.about.is_some(), &self.about),
+            (
+                "activities",
+                UserField::Activities,
+                self.activities.is_some(),
+                &self.activities,
+            ),
+            ("bdate", UserField::Bdate, self.bdate.is_some(), &bdate),
+            ("books", UserField::Books, self.books.is_some(), &self.books),
+            (
+                "domain",
+                UserField::Domain,
+                self.domain.is_some(),
+                &self.domain,
+            ),
+            (
+                "followers_count",
+                UserField::FollowersCount,
+                self.followers_count.is_some(),
+                &self.followers_count,
+            ),
+            ("games", UserField::Games, self.games.is_some(), &self.games),
+            (
+                "has_mobile",
+                UserField::HasMobile,
+                self.has_mobile.is_some(),
+                &self.has_mobile,
+            ),
+            (
+                "has_photo",
+                UserField::HasPhoto,
+                self.has_photo.is_some(),
+                &self.has_photo,
+            ),
+            (
+                "home_town",
+                UserField::HomeTown,
+                self.home_town.is_some(),
+                &self.home_town,
+            ),
+            (
+                "interests",
+                UserField::Interests,
+                self.interests.is_some(),
+                &self.interests,
+            ),
+            (
+                "maiden_name",
+                UserField::MaidenName,
+                self.maiden_name.is_some(),
+                &self.maiden_name,
+            ),
+            (
+                "movies",
+                UserField::Movies,
+                self.movies.is_some(),
+                &self.movies,
+            ),
+            ("music", UserField::Music, self.music.is_some(), &self.music),
+            (
+                "nickname",
+                UserField::Nickname,
+                self.nickname.is_some(),
+                &self.nickname,
+            ),
+            (
+                "photo_max_orig",
+                UserField::PhotoMaxOrig,
+                self.photo_max_orig.is_some(),
+                &self.photo_max_orig,
+            ),
+            (
+                "quotes",
+                UserField::Quotes,
+                self.quotes.is_some(),
+                &self.quotes,
+            ),
+            (
+                "screen_name",
+                UserField::ScreenName,
+                self.screen_name.is_some(),
+                &self.screen_name,
+            ),
+            ("sex", UserField::Sex, self.sex.is_some(), &self.sex),
+            ("site", UserField::Site, self.site.is_some(), &self.site),
+            (
+                "status",
+                UserField::Status,
+                self.status.is_some(),
+                &self.status,
+            ),
+            ("tv", UserField::Tv, self.tv.is_some(), &self.tv),
+            (
+                "verified",
+                UserField::Verified,
+                self.verified.is_some(),
+                &self.verified,
+            ),
+            (
+                "skype",
+                UserField::Connections,
+                self.skype.is_some(),
+                &self.skype,
+            ),
+            (
+                "facebook",
+                UserField::Connections,
+                self.facebook.is_some(),
+                &self.facebook,
+            ),
+            (
+                "twitter",
+                UserField::Connections,
+                self.twitter.is_some(),
+                &self.twitter,
+            ),
+            (
+                "livejournal",
+                UserField::Connections,
+                self.livejournal.is_some(),
+                &self.livejournal,
+            ),
+            (
+                "instagram",
+                UserField::Connections,
+                self.instagram.is_some(),
+                &self.instagram,
+            ),
+            (
+                "relation",
+                UserField::Relation,
+                self.relation.is_some(),
+                &self.relation,
+            ),
+            (
+                "bdate_day",
+                UserField::Bdate,
+                self.bdate.is_some(),
+                &bdate_day,
+            ),
+            (
+                "bdate_month",
+                UserField::Bdate,
+                self.bdate.is_some(),
+                &bdate_month,
+            ),
+            (
+                "bdate_year",
+                UserField::Bdate,
+                self.bdate.is_some(),
+                &bdate_year,
+            ),
+        ];
+        let mut columns: Vec<(&str, &dyn ToSql)> = vec![
+            ("id", &self.id),
+            ("first_name", &self.first_name),
+            ("last_name", &self.last_name),
+            ("deactivated", &self.deactivated),
+            ("is_closed", &self.is_closed),
+            ("fetched_at", &fetched_at),
+        ];
+        columns.extend(
+            optional
+                .iter()
+                .filter(|(_, field, present, _)| self.was_fetched(*field, *present))
+                .map(|(column, _, _, value)| (*column, *value)),
+        );
+
+        let query = format!(
+            "INSERT INTO {} ({}) VALUES ({}) ON CONFLICT (id) DO UPDATE SET {}",
+            table_name,
+            columns.iter().map(|e| e.0).join(", "),
+            columns.iter().map(|_| "?").join(", "),
+            columns
+                .iter()
+                .skip(1)
+                .map(|e| format!("{0} = excluded.{0}", e.0))
+                .join(", ")
+        );
+
+        if let Err(source) = connection.execute(&query, columns.iter().map(|e| e.1)) {
+            return Err(RobberError::StoreError(StoreError {
+                user_id: self.id,
+                table: table_name.to_string(),
+                source,
+            }));
+        }
+
+        // Rows of a field that was not fetched this time stay as they were.
+        let nested = [
+            ("career", UserField::Career, self.career.is_some()),
+            ("city", UserField::City, self.city.is_some()),
+            ("contacts", UserField::Contacts, self.contacts.is_some()),
+            ("counters", UserField::Counters, self.counters.is_some()),
+            ("country", UserField::Country, self.country.is_some()),
+            ("education", UserField::Education, self.education.is_some()),
+            ("last_seen", UserField::LastSeen, self.last_seen.is_some()),
+            ("military", UserField::Military, self.military.is_some()),
+            (
+                "occupation",
+                UserField::Occupation,
+                self.occupation.is_some(),
+            ),
+            ("personal", UserField::Personal, self.personal.is_some()),
+            (
+                "relation_partner",
+                UserField::Relation,
+                self.relation_partner.is_some(),
+            ),
+            ("relatives", UserField::Relatives, self.relatives.is_some()),
+            ("schools", UserField::Schools, self.schools.is_some()),
+            (
+                "universities",
+                UserField::Universities,
+                self.universities.is_some(),
+            ),
+        ];
+        for (table, field, present) in nested.iter() {
+            if !self.was_fetched(*field, *present) {
+                continue;
+            }
+            let query = format!("DELETE FROM {} WHERE user_id = ?", table);
+            if let Err(source) = connection.execute(&query, params![self.id]) {
+                return Err(RobberError::StoreError(StoreError {
+                    user_id: self.id,
+                    table: table.to_string(),
+                    source,
+                }));
+            }
         }
 
         try_save!(self.career, career, connection, "career", self.id)?;
@@ -872,79 +1967,335 @@ impl User {
     }
 }
 
-#[derive(Deserialize)]
-pub struct UserGet {
-    response: Option<Vec<User>>,
+const USER_COLUMNS: &str = "id, first_name, last_name, deactivated, is_closed, about, activities, bdate, books, domain, followers_count, games, has_mobile, has_photo, home_town, interests, maiden_name, movies, music, nickname, photo_max_orig, quotes, screen_name, sex, site, status, tv, verified, skype, facebook, twitter, livejournal, instagram, relation, fetched_at";
+
+/// Users read per query by `UserIter`.
+const LOAD_BATCH_SIZE: i64 = 100;
+
+impl User {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        // Rows stored before bdate was checked may hold dates that do not parse.
+        let bdate = text(row, 7)?.and_then(|e| e.parse::<BirthDate>().ok());
+
+        Ok(Self {
+            id: row.get(0)?,
+            first_name: row.get(1)?,
+            last_name: row.get(2)?,
+            deactivated: row.get(3)?,
+            is_closed: row.get(4)?,
+            about: row.get(5)?,
+            activities: row.get(6)?,
+            bdate,
+            books: row.get(8)?,
+            domain: row.get(9)?,
+            followers_count: row.get(10)?,
+            games: row.get(11)?,
+            has_mobile: row.get(12)?,
+            has_photo: row.get(13)?,
+            home_town: row.get(14)?,
+            interests: row.get(15)?,
+            maiden_name: row.get(16)?,
+            movies: row.get(17)?,
+            music: row.get(18)?,
+            nickname: row.get(19)?,
+            photo_max_orig: row.get(20)?,
+            quotes: row.get(21)?,
+            screen_name: row.get(22)?,
+            sex: row.get(23)?,
+            site: row.get(24)?,
+            status: row.get(25)?,
+            tv: row.get(26)?,
+            verified: integer(row, 27)?,
+            skype: row.get(28)?,
+            facebook: row.get(29)?,
+            twitter: row.get(30)?,
+            livejournal: row.get(31)?,
+            instagram: row.get(32)?,
+            relation: row.get(33)?,
+            fetched_at: row.get(34)?,
+            ..Default::default()
+        })
+    }
+
+    /// Fills the values `store` put in the other tables.
+    ///
+    /// Empty lists are read back as missing, and a single career or military
+    /// entry comes back as a list of one.
+    fn load_nested(mut self, connection: &Connection) -> rusqlite::Result<Self> {
+        let id = self.id;
+
+        self.career = non_empty(load_rows(connection, "career", id)?).map(Career::Many);
+        self.city = load_one(connection, "city", id)?;
+        self.counters = load_one(connection, "counters", id)?;
+        self.country = load_one(connection, "country", id)?;
+        self.education = load_one(connection, "education", id)?;
+        self.last_seen = load_one(connection, "last_seen", id)?;
+        self.personal = load_one(connection, "personal", id)?.map(Personal::Value);
+        // Always present on users from the API, as its fields are flattened into the user.
+        self.contacts = Some(load_one(connection, "contacts", id)?.unwrap_or(Contacts {
+            mobile_phone: None,
+            home_phone: None,
+        }));
+        self.military = non_empty(load_rows(connection, "military", id)?).map(Military::Many);
+        self.occupation = load_one(connection, "occupation", id)?;
+        self.relatives = non_empty(load_rows(connection, "relatives", id)?).map(Relatives::Value);
+        self.relation_partner = load_one(connection, "relation_partner", id)?;
+        self.schools = non_empty(load_rows(connection, "schools", id)?).map(Schools::Value);
+        self.universities =
+            non_empty(load_rows(connection, "universities", id)?).map(Universities::Value);
+
+        Ok(self)
+    }
+
+    /// Reads the user `id` back from the tables written by `User::store` into `objects`.
+    pub fn load(connection: &Connection, id: i64) -> Result<Option<User>, RobberError> {
+        let query = format!("SELECT {} FROM objects WHERE id = ?", USER_COLUMNS);
+        let user = connection
+            .prepare_cached(&query)
+            .and_then(|mut e| e.query_row(params![id], User::from_row).optional())
+            .map_err(RobberError::SqliteError)?;
+
+        match user {
+            Some(user) => user
+                .load_nested(connection)
+                .map(Some)
+                .map_err(RobberError::SqliteError),
+            None => Ok(None),
+        }
+    }
+
+    /// Users of `ids` that are stored, in the order of `ids`.
+    pub fn load_many(connection: &Connection, ids: &[i64]) -> Result<Vec<User>, RobberError> {
+        let mut users = Vec::with_capacity(ids.len());
+        for id in ids {
+            if let Some(user) = User::load(connection, *id)? {
+                users.push(user);
+            }
+        }
+        Ok(users)
+    }
+
+    /// Ids of stored users fetched more than `older_than` ago, or at an unknown time.
+    pub fn stale_ids(
+        connection: &Connection,
+        older_than: Duration,
+    ) -> Result<Vec<i64>, RobberError> {
+        let threshold = unix_time() - older_than.as_secs() as i64;
+
+        let mut statement = connection
+            .prepare(
+                "SELECT id FROM objects WHERE fetched_at IS NULL OR fetched_at < ? ORDER BY id",
+            )
+            .map_err(RobberError::SqliteError)?;
+        let ids = statement
+            .query_map(params![threshold], |row| row.get(0))
+            .map_err(RobberError::SqliteError)?;
+        ids.collect::<Result<_, _>>()
+            .map_err(RobberError::SqliteError)
+    }
+
+    /// Every stored user by ascending id, read a batch at a time.
+    pub fn load_all(connection: &Connection) -> UserIter<'_> {
+        UserIter {
+            connection,
+            last_id: None,
+            batch: VecDeque::new(),
+            done: false,
+        }
+    }
+}
+
+/// Iterator returned by `User::load_all`.
+pub struct UserIter<'a> {
+    connection: &'a Connection,
+    last_id: Option<i64>,
+    batch: VecDeque<User>,
+    done: bool,
+}
+
+impl<'a> UserIter<'a> {
+    fn next_batch(&mut self) -> rusqlite::Result<()> {
+        let query = format!(
+            "SELECT {} FROM objects WHERE id > ? ORDER BY id LIMIT ?",
+            USER_COLUMNS
+        );
+        let mut statement = self.connection.prepare_cached(&query)?;
+        let users = statement
+            .query_map(
+                params![self.last_id.unwrap_or(i64::MIN), LOAD_BATCH_SIZE],
+                User::from_row,
+            )?
+            .collect::<rusqlite::Result<Vec<User>>>()?;
+
+        self.done = (users.len() as i64) < LOAD_BATCH_SIZE;
+        self.last_id = users.last().map(|e| e.id).or(self.last_id);
+        for user in users {
+            self.batch.push_back(user.load_nested(self.connection)?);
+        }
+        Ok(())
+    }
+}
+
+impl<'a> Iterator for UserIter<'a> {
+    type Item = Result<User, RobberError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.batch.is_empty() && !self.done {
+            if let Err(e) = self.next_batch() {
+                self.done = true;
+                return Some(Err(RobberError::SqliteError(e)));
+            }
+        }
+        self.batch.pop_front().map(Ok)
+    }
+}
+
+/// Builds a `User` field by field, see `User::builder`.
+#[derive(Debug, Clone)]
+pub struct UserBuilder {
+    user: User,
+}
+
+impl UserBuilder {
+    setter!(deactivated: String);
+    setter!(is_closed: bool);
+    setter!(about: String);
+    setter!(activities: String);
+    setter!(bdate: BirthDate);
+    setter!(books: String);
+    setter!(career: Career);
+    setter!(city: City);
+    setter!(skype: String);
+    setter!(facebook: String);
+    setter!(twitter: String);
+    setter!(livejournal: String);
+    setter!(instagram: String);
+    setter!(contacts: Contacts);
+    setter!(counters: Counters);
+    setter!(country: Country);
+    setter!(domain: String);
+    setter!(education: EducationInfo);
+    setter!(followers_count: i64);
+    setter!(games: String);
+    setter!(has_mobile: i64);
+    setter!(has_photo: i64);
+    setter!(home_town: String);
+    setter!(interests: String);
+    setter!(last_seen: LastSeen);
+    setter!(maiden_name: String);
+    setter!(military: Military);
+    setter!(movies: String);
+    setter!(music: String);
+    setter!(nickname: String);
+    setter!(occupation: Occupation);
+    setter!(personal: Personal);
+    setter!(photo_max_orig: String);
+    setter!(quotes: String);
+    setter!(relatives: Relatives);
+    setter!(relation: Relation);
+    setter!(relation_partner: RelationPartner);
+    setter!(schools: Schools);
+    setter!(screen_name: String);
+    setter!(sex: Sex);
+    setter!(site: String);
+    setter!(status: String);
+    setter!(tv: String);
+    setter!(universities: Universities);
+    setter!(verified: i64);
+    setter!(fetched_at: i64);
+    setter!(fetched_fields: FieldSet);
+
+    pub fn build(self) -> User {
+        self.user
+    }
 }
 
 const USERS_PER_REQUEST: usize = 1000;
 
+/// `fields` may be a `FieldSet`, a `UserField` or the comma-separated string form.
 #[async_trait]
 pub trait UserInteraction {
-    async fn get_user(&self, user_id: i32, fields: &str) -> Result<User, RobberError>;
-    async fn get_users(&self, user_ids: &[i32], fields: &str) -> Result<Vec<User>, RobberError>;
-    async fn get_users_unchecked(
+    async fn get_user<F>(&self, user_id: i32, fields: F) -> Result<User, RobberError>
+    where
+        F: Into<FieldSet> + Send;
+    async fn get_users<F>(&self, user_ids: &[i32], fields: F) -> Result<Vec<User>, RobberError>
+    where
+        F: Into<FieldSet> + Send;
+    async fn get_users_unchecked<F>(
+        &self,
+        user_ids: &[i32],
+        fields: F,
+    ) -> Result<Vec<User>, RobberError>
+    where
+        F: Into<FieldSet> + Send;
+    /// Same as `get_users_unchecked`, taking the field set by reference so callers
+    /// fetching many chunks with the same fields do not convert them for every call.
+    async fn get_users_chunk(
         &self,
         user_ids: &[i32],
-        fields: &str,
+        fields: &FieldSet,
     ) -> Result<Vec<User>, RobberError>;
 }
 
 #[async_trait]
 impl UserInteraction for ApiManager {
-    async fn get_user(&self, user_id: i32, fields: &str) -> Result<User, RobberError> {
-        let result = self.get_users(&[user_id], fields).await;
-        result.map(|mut e| e.pop().unwrap())
+    async fn get_user<F>(&self, user_id: i32, fields: F) -> Result<User, RobberError>
+    where
+        F: Into<FieldSet> + Send,
+    {
+        // VK leaves unknown ids out of the response instead of failing.
+        self.get_users(&[user_id], fields)
+            .await?
+            .pop()
+            .ok_or(RobberError::UserNotFound { user_id })
     }
 
-    async fn get_users(&self, user_ids: &[i32], fields: &str) -> Result<Vec<User>, RobberError> {
+    async fn get_users<F>(&self, user_ids: &[i32], fields: F) -> Result<Vec<User>, RobberError>
+    where
+        F: Into<FieldSet> + Send,
+    {
+        let fields = fields.into();
         let mut users: Vec<User> = Vec::with_capacity(user_ids.len());
         for chunk in user_ids.chunks(USERS_PER_REQUEST) {
-            let ids: String = chunk
-                .iter()
-                .map(i32::to_string)
-                .collect::<Vec<String>>()
-                .join(", ");
-            let resp = self
-                .request_json::<_, UserGet>(
-                    "users.get",
-                    &[("user_ids", ids.as_str()), ("fields", fields)],
-                )
-                .await?;
-            
-            println!("{:?} {:?}", ids, fields);
-
-            match resp.response {
-                Some(mut e) => users.extend(e.drain(..)),
-                None => return Err(RobberError::APIError),
-            }
+            let mut resp = self.get_users_chunk(chunk, &fields).await?;
 
-            tokio::time::sleep(tokio::time::Duration::from_millis(API_TIMEOUT_MS)).await;
+            users.append(&mut resp);
         }
         Ok(users)
     }
 
-    async fn get_users_unchecked(
+    async fn get_users_unchecked<F>(
+        &self,
+        user_ids: &[i32],
+        fields: F,
+    ) -> Result<Vec<User>, RobberError>
+    where
+        F: Into<FieldSet> + Send,
+    {
+        self.get_users_chunk(user_ids, &fields.into()).await
+    }
+
+    async fn get_users_chunk(
         &self,
         user_ids: &[i32],
-        fields: &str,
+        fields: &FieldSet,
     ) -> Result<Vec<User>, RobberError> {
+        let names = fields.to_string();
         let ids = user_ids
             .iter()
             .map(i32::to_string)
             .collect::<Vec<String>>()
             .join(", ");
-        match self
-            .request_json::<_, UserGet>(
+        let mut users = self
+            .call::<Vec<User>>(
                 "users.get",
-                &[("user_ids", ids.as_str()), ("fields", fields)],
+                &[("user_ids", ids.as_str()), ("fields", names.as_str())],
             )
-            .await?
-            .response
-        {
-            Some(e) => Ok(e),
-            None => Err(RobberError::APIError),
+            .await?;
+        for user in &mut users {
+            user.fetched_fields = Some(fields.clone());
         }
+        Ok(users)
     }
 }
 
@@ -0,0 +1,80 @@
+use cute_fox::{
+    requests::{api_manager::VkResponse, errors::VkApiError},
+    RobberError,
+};
+
+#[test]
+fn test_deserialize_auth_failed() {
+    let data = r#"
+    {
+        "error_code": 5,
+        "error_msg": "User authorization failed: invalid access_token (4).",
+        "request_params": [
+            { "key": "method", "value": "users.get" },
+            { "key": "oauth", "value": "1" },
+            { "key": "v", "value": "5.130" }
+        ]
+    }
+    "#;
+
+    let error: VkApiError = serde_json::from_str(data).unwrap();
+
+    assert!(matches!(error, VkApiError::AuthFailed(_)));
+    assert_eq!(error.code(), 5);
+    assert_eq!(
+        error.message(),
+        "User authorization failed: invalid access_token (4)."
+    );
+    assert_eq!(error.request_params().len(), 3);
+    assert_eq!(error.request_params()[0].value, "users.get");
+}
+
+#[test]
+fn test_deserialize_captcha_needed() {
+    let data = r#"
+    {
+        "error_code": 14,
+        "error_msg": "Captcha needed",
+        "request_params": [],
+        "captcha_sid": "479261466155",
+        "captcha_img": "https://api.vk.com/captcha.php?sid=479261466155"
+    }
+    "#;
+
+    let error: VkApiError = serde_json::from_str(data).unwrap();
+
+    assert!(matches!(error, VkApiError::CaptchaNeeded(_)));
+    assert_eq!(error.details().captcha_sid.as_deref(), Some("479261466155"));
+}
+
+#[test]
+fn test_deserialize_unknown_code() {
+    let data = r#"{ "error_code": 3610, "error_msg": "User is deactivated" }"#;
+
+    let error: VkApiError = serde_json::from_str(data).unwrap();
+
+    assert!(matches!(error, VkApiError::Other(_)));
+    assert_eq!(error.code(), 3610);
+    assert!(error.request_params().is_empty());
+}
+
+#[test]
+fn test_response_envelope() {
+    let ok: VkResponse<Vec<i32>> = serde_json::from_str(r#"{ "response": [1, 2, 3] }"#).unwrap();
+    assert_eq!(ok.into_result().unwrap(), vec![1, 2, 3]);
+
+    let failed: VkResponse<Vec<i32>> = serde_json::from_str(
+        r#"{ "error": { "error_code": 6, "error_msg": "Too many requests per second" } }"#,
+    )
+    .unwrap();
+    assert!(matches!(
+        failed.into_result(),
+        Err(RobberError::APIError(VkApiError::TooManyRequests(_)))
+    ));
+
+    let empty: VkResponse<Vec<i32>> = serde_json::from_str("{}").unwrap();
+    assert!(matches!(
+        empty.into_result(),
+        Err(RobberError::SerdeError(_))
+    ));
+}
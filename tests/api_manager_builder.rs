@@ -0,0 +1,31 @@
+use std::time::Duration;
+
+use cute_fox::{
+    requests::{api_manager::ApiManager, retry::RetryPolicy},
+    RobberError,
+};
+
+#[test]
+fn test_build() {
+    let api = ApiManager::builder("token")
+        .version("5.131")
+        .base_url("http://127.0.0.1:8080/method/")
+        .lang("en")
+        .user_agent("cute_fox")
+        .proxy("http://127.0.0.1:3128")
+        .connect_timeout(Duration::from_secs(5))
+        .timeout(Duration::from_secs(30))
+        .retry_policy(RetryPolicy::none())
+        .build()
+        .unwrap();
+
+    assert_eq!(api.base_url(), "http://127.0.0.1:8080/method");
+    assert_eq!(api.retry_policy().max_attempts, 1);
+}
+
+#[test]
+fn test_invalid_proxy() {
+    let result = ApiManager::builder("token").proxy("not a proxy").build();
+
+    assert!(matches!(result, Err(RobberError::ReqwestError(_))));
+}
@@ -0,0 +1,136 @@
+mod support;
+
+use cute_fox::{migrations, stages::birth_date::BirthDate, CuteValue, SqliteStorage};
+use rusqlite::{Connection, NO_PARAMS};
+use serde_json::json;
+
+/// `bdate_day`, `bdate_month` and `bdate_year`.
+type Split = (Option<u8>, Option<u8>, Option<u16>);
+
+#[test]
+fn test_parse_and_display() {
+    let date: BirthDate = "26.7.2004".parse().unwrap();
+    assert_eq!((date.day(), date.month(), date.year()), (26, 7, Some(2004)));
+    assert_eq!(date.to_string(), "26.7.2004");
+
+    let date: BirthDate = "21.11".parse().unwrap();
+    assert_eq!(date.year(), None);
+    assert_eq!(date.to_string(), "21.11");
+
+    assert!("29.2".parse::<BirthDate>().is_ok());
+    assert!("29.2.2000".parse::<BirthDate>().is_ok());
+    for invalid in &[
+        "29.2.2001",
+        "31.4.1990",
+        "0.1.1990",
+        "1.13",
+        "1.1.0",
+        "26",
+        "a.b",
+        "1.1.1990.1",
+    ] {
+        let error = invalid.parse::<BirthDate>().unwrap_err();
+        assert_eq!(error.input, *invalid);
+    }
+}
+
+#[test]
+fn test_age() {
+    let date = BirthDate::new(26, 7, Some(2004)).unwrap();
+    assert_eq!(date.age_on(2021, 7, 25), Some(16));
+    assert_eq!(date.age_on(2021, 7, 26), Some(17));
+    assert_eq!(date.age_on(2004, 1, 1), None);
+    assert_eq!(
+        BirthDate::new(26, 7, None).unwrap().age_on(2021, 1, 1),
+        None
+    );
+}
+
+#[test]
+fn test_user_bdate() {
+    let user = |bdate| {
+        serde_json::from_value::<cute_fox::stages::users::User>(
+            json!({ "id": 1, "first_name": "A", "last_name": "B", "bdate": bdate }),
+        )
+    };
+
+    assert_eq!(
+        serde_json::to_value(user("5.3.1990").unwrap()).unwrap()["bdate"],
+        "5.3.1990"
+    );
+    assert!(serde_json::to_value(user("").unwrap()).unwrap()["bdate"].is_null());
+    assert!(serde_json::to_value(user("31.2.1990").unwrap()).unwrap()["bdate"].is_null());
+
+    // The other users of the response still load.
+    let users: Vec<cute_fox::stages::users::User> = serde_json::from_value(json!([
+        { "id": 1, "first_name": "A", "last_name": "B", "bdate": "31.2.1990" },
+        { "id": 2, "first_name": "C", "last_name": "D", "bdate": "1.2.1990" },
+    ]))
+    .unwrap();
+    assert_eq!(users[0].bdate(), None);
+    assert_eq!(users[1].bdate().unwrap().day(), 1);
+}
+
+#[test]
+fn test_storage_columns() {
+    let mut conn = Connection::open_in_memory().unwrap();
+    CuteValue::Users(support::fixture_users())
+        .save(&mut conn, 10)
+        .unwrap();
+
+    let mut statement = conn
+        .prepare("SELECT id, bdate, bdate_day, bdate_month, bdate_year FROM objects ORDER BY id")
+        .unwrap();
+    let rows: Vec<(i64, Option<String>, Split)> = statement
+        .query_map(NO_PARAMS, |row| {
+            Ok((
+                row.get(0)?,
+                row.get(1)?,
+                (row.get(2)?, row.get(3)?, row.get(4)?),
+            ))
+        })
+        .unwrap()
+        .map(Result::unwrap)
+        .collect();
+
+    assert_eq!(
+        rows[0],
+        (
+            1,
+            Some("10.10.1984".to_string()),
+            (Some(10), Some(10), Some(1984))
+        )
+    );
+    assert!(rows
+        .iter()
+        .any(|e| e.1.as_deref() == Some("21.11") && e.2 == (Some(21), Some(11), None)));
+}
+
+#[test]
+fn test_migration_splits_existing_dates() {
+    let path = support::temp_copy("data/database.db");
+    let mut conn = Connection::open(&path).unwrap();
+    conn.execute_batch(
+        "INSERT INTO objects (id, first_name, last_name, bdate) VALUES (1, 'A', 'B', '26.7.2004');
+         INSERT INTO objects (id, first_name, last_name, bdate) VALUES (2, 'C', 'D', '21.11');
+         INSERT INTO objects (id, first_name, last_name, bdate) VALUES (3, 'E', 'F', 'garbage');",
+    )
+    .unwrap();
+
+    migrations::migrate(&mut conn).unwrap();
+
+    let split = |id: i64| -> Split {
+        conn.query_row(
+            "SELECT bdate_day, bdate_month, bdate_year FROM objects WHERE id = ?",
+            [id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .unwrap()
+    };
+    assert_eq!(split(1), (Some(26), Some(7), Some(2004)));
+    assert_eq!(split(2), (Some(21), Some(11), None));
+    assert_eq!(split(3), (None, None, None));
+
+    drop(conn);
+    std::fs::remove_file(path).unwrap();
+}
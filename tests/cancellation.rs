@@ -0,0 +1,135 @@
+mod support;
+
+use std::{sync::Arc, time::Duration};
+
+use cute_fox::{
+    cancel::CancelToken, jobs, requests::rate_limiter::RateLimiter, stages::fields::FieldSet,
+    CuteExecutor, CuteFox, CuteTask, CuteValue, RobberError, SaveMode,
+};
+use rusqlite::Connection;
+use support::{users_job, MockVk};
+
+#[tokio::test]
+async fn test_cancelled_wakes_every_clone() {
+    let cancel = CancelToken::new();
+    let waiter = tokio::spawn({
+        let cancel = cancel.clone();
+        async move { cancel.cancelled().await }
+    });
+    assert!(!cancel.is_cancelled());
+
+    cancel.clone().cancel();
+    tokio::time::timeout(Duration::from_secs(1), waiter)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(cancel.is_cancelled());
+    // Completes right away once cancelled.
+    cancel.cancelled().await;
+}
+
+#[tokio::test]
+async fn test_cancelled_task_sends_no_requests() {
+    let vk = MockVk::start().await;
+    let path = support::temp_database();
+    let cancel = CancelToken::new();
+    cancel.cancel();
+    let fox = CuteFox::from_managers(vec![vk.manager("token")]).with_database(&path, 10);
+
+    let error = fox
+        .execute_with(
+            CuteTask::GetUsers {
+                user_ids: (1..=5000).collect(),
+                fields: FieldSet::basic(),
+            },
+            cancel.clone(),
+        )
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        error,
+        RobberError::Interrupted(ref value) if matches!(**value, CuteValue::Users(ref e) if e.is_empty())
+    ));
+
+    let error = fox
+        .run_pipeline_with(
+            CuteTask::GetUsers {
+                user_ids: (1..=5000).collect(),
+                fields: FieldSet::basic(),
+            },
+            SaveMode::Abort,
+            cancel,
+        )
+        .await
+        .unwrap_err();
+    assert!(matches!(error, RobberError::Interrupted(_)));
+    assert_eq!(vk.calls("users.get"), 0);
+
+    // The token only stopped those tasks, the next one runs to the end.
+    let value = fox
+        .execute(CuteTask::GetUsers {
+            user_ids: vec![1, 2],
+            fields: FieldSet::basic(),
+        })
+        .await
+        .unwrap();
+    assert!(matches!(value, CuteValue::Users(ref e) if e.len() == 2));
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[tokio::test]
+async fn test_cancelled_job_resumes_where_it_stopped() {
+    let vk = Arc::new(MockVk::start().await);
+    let path = support::temp_database();
+    let cancel = CancelToken::new();
+    // One request every 100ms, so the job is cancelled well before it finishes.
+    let manager = vk
+        .builder("token")
+        .rate_limiter(Arc::new(RateLimiter::with_burst(10, 1)))
+        .build()
+        .unwrap();
+    let fox = CuteFox::from_managers(vec![manager]).with_database(&path, 10);
+
+    let canceller = tokio::spawn({
+        let vk = vk.clone();
+        let cancel = cancel.clone();
+        async move {
+            while vk.calls("users.get") < 2 {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+            cancel.cancel();
+        }
+    });
+    let error = fox
+        .execute_with(users_job("all", (1..=10_000).collect()), cancel)
+        .await
+        .unwrap_err();
+    canceller.await.unwrap();
+
+    let job = match error {
+        RobberError::Interrupted(value) => match *value {
+            CuteValue::Job(e) => e,
+            e => panic!("expected a job, got {:?}", e),
+        },
+        e => panic!("expected an interruption, got {:?}", e),
+    };
+    // Requests in flight finished and were recorded, no other one was sent.
+    let fetched = vk.calls("users.get");
+    assert!(fetched < 10);
+    assert_eq!(job.done, fetched);
+    assert_eq!(job.finished_at, None);
+
+    let fox = CuteFox::from_managers(vec![vk.manager("token")]).with_database(&path, 10);
+    let value = fox.execute(users_job("all", vec![1])).await.unwrap();
+    assert!(matches!(value, CuteValue::Job(ref e) if e.finished_at.is_some()));
+    assert_eq!(vk.calls("users.get"), 10);
+
+    let conn = Connection::open(&path).unwrap();
+    let job = jobs::find(&conn, "all").unwrap().unwrap();
+    assert_eq!(job.done, 10);
+    assert!(job.finished_at.is_some());
+
+    drop(conn);
+    std::fs::remove_file(path).unwrap();
+}
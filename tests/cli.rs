@@ -0,0 +1,128 @@
+mod support;
+
+use std::process::{Command, Output};
+
+use rusqlite::{Connection, NO_PARAMS};
+
+const EXIT_USAGE: i32 = 2;
+
+/// Runs the CLI without any `CUTE_FOX_*` variable of the environment.
+fn cute_fox(args: &[&str]) -> Output {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_cute_fox"));
+    for (key, _) in std::env::vars() {
+        if key.starts_with(cute_fox::config::ENV_PREFIX) {
+            command.env_remove(key);
+        }
+    }
+    command.args(args).output().unwrap()
+}
+
+fn assert_usage_error(args: &[&str], message: &str) {
+    let output = cute_fox(args);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert_eq!(output.status.code(), Some(EXIT_USAGE), "{}", stderr);
+    assert!(stderr.contains(message), "{}", stderr);
+}
+
+#[test]
+fn test_usage_errors() {
+    assert_usage_error(&[], "USAGE");
+    assert_usage_error(&["users", "1", "ten"], "expected an integer");
+    assert_usage_error(&["user", "one"], "expected an integer");
+    assert_usage_error(&["members", "club"], "expected an integer");
+    assert_usage_error(&["users", "1", "10"], "`tokens`");
+    assert_usage_error(
+        &[
+            "--access_token",
+            "token",
+            "users",
+            "1",
+            "10",
+            "--field",
+            "sexx",
+        ],
+        "unknown user field `sexx`",
+    );
+    assert_usage_error(
+        &[
+            "--access_token",
+            "token",
+            "users",
+            "1",
+            "10",
+            "--preset",
+            "none",
+        ],
+        "`fields.none`",
+    );
+    assert_usage_error(
+        &[
+            "--access_token",
+            "token",
+            "users",
+            "1",
+            "10",
+            "--job",
+            "all",
+        ],
+        "jobs need a database",
+    );
+    assert_usage_error(&["--access_token", "token", "refresh"], "needs a database");
+    let path = support::temp_database();
+    assert_usage_error(
+        &[
+            "--access_token",
+            "token",
+            "refresh",
+            "--database_path",
+            path.to_str().unwrap(),
+        ],
+        "refresh needs --field",
+    );
+    std::fs::remove_file(path).ok();
+    assert_usage_error(&["init-db"], "needs a database");
+    assert_usage_error(&["export"], "needs a database");
+}
+
+#[test]
+fn test_init_db() {
+    let path = std::env::temp_dir().join(format!("cute_fox_cli_{}.db", std::process::id()));
+    std::fs::remove_file(&path).ok();
+
+    let output = cute_fox(&[
+        "init-db",
+        "--history",
+        "--database_path",
+        path.to_str().unwrap(),
+    ]);
+    assert!(output.status.success());
+
+    let conn = Connection::open(&path).unwrap();
+    let tables: i64 = conn
+        .query_row(
+            "SELECT COUNT(*) FROM sqlite_master WHERE name IN ('objects', 'user_history')",
+            NO_PARAMS,
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(tables, 2);
+
+    std::fs::remove_file(path).ok();
+}
+
+#[test]
+fn test_export_prints_stored_users() {
+    let path = support::temp_database();
+    let output = cute_fox(&[
+        "export",
+        "--format",
+        "jsonl",
+        "--database_path",
+        path.to_str().unwrap(),
+    ]);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).is_empty());
+
+    std::fs::remove_file(path).ok();
+}
@@ -0,0 +1,144 @@
+use cute_fox::{requests::api_manager::API_VERSION, Config, CuteFox, RobberError};
+
+fn error_key(error: RobberError) -> String {
+    match error {
+        RobberError::ConfigError { key, .. } => key,
+        e => panic!("expected a configuration error, got {:?}", e),
+    }
+}
+
+#[test]
+fn test_toml_and_json() {
+    let toml = Config::from_toml_str(
+        r#"
+        tokens = ["first", "second"]
+        requests_per_second = 20
+        transaction_size = 500
+
+        [fields]
+        basic = ["sex", "bdate"]
+        "#,
+    )
+    .unwrap();
+    let json = Config::from_json_str(
+        r#"{
+            "tokens": ["first", "second"],
+            "requests_per_second": 20,
+            "transaction_size": 500,
+            "fields": { "basic": ["sex", "bdate"] }
+        }"#,
+    )
+    .unwrap();
+
+    for config in &[toml, json] {
+        assert_eq!(config.tokens().unwrap(), vec!["first", "second"]);
+        assert_eq!(config.api_version, API_VERSION);
+        assert_eq!(config.requests_per_second, 20);
+        assert_eq!(config.transaction_size, 500);
+        assert_eq!(config.database_path, None);
+        assert_eq!(config.fields("basic").unwrap().to_string(), "sex,bdate");
+        config.validate().unwrap();
+    }
+
+    let error = Config::from_toml_str("tokenz = []").unwrap_err();
+    assert!(error.to_string().contains("tokenz"), "{}", error);
+}
+
+#[test]
+fn test_token_files() {
+    let dir = std::env::temp_dir().join(format!("cute_fox_config_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(
+        dir.join("tokens.txt"),
+        "# user tokens\nfirst\n\n  second  \n",
+    )
+    .unwrap();
+    std::fs::write(
+        dir.join("cute_fox.toml"),
+        "tokens = [\"inline\"]\ntoken_files = [\"tokens.txt\", \"missing.txt\"]\ndatabase_path = \"users.db\"",
+    )
+    .unwrap();
+
+    let mut config = Config::load(dir.join("cute_fox.toml")).unwrap();
+    assert_eq!(config.database_path, Some(dir.join("users.db")));
+    assert_eq!(error_key(config.tokens().unwrap_err()), "token_files[1]");
+
+    config.token_files.pop();
+    assert_eq!(config.tokens().unwrap(), vec!["inline", "first", "second"]);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_env_overrides() {
+    let mut config = Config::from_toml_str("tokens = [\"file\"]\ntransaction_size = 10").unwrap();
+
+    config
+        .apply_vars(vec![
+            ("CUTE_FOX_TOKENS", "first, second"),
+            ("CUTE_FOX_TRANSACTION_SIZE", "50"),
+            ("CUTE_FOX_DATABASE_PATH", "users.db"),
+            ("HOME", "/root"),
+        ])
+        .unwrap();
+    assert_eq!(config.tokens().unwrap(), vec!["first", "second"]);
+    assert_eq!(config.transaction_size, 50);
+    assert_eq!(config.database_path, Some("users.db".into()));
+
+    let error = config
+        .apply_vars(vec![("CUTE_FOX_REQUESTS_PER_SECOND", "fast")])
+        .unwrap_err();
+    assert_eq!(error_key(error), "CUTE_FOX_REQUESTS_PER_SECOND");
+
+    let error = config
+        .apply_vars(vec![("CUTE_FOX_TOKEN", "typo")])
+        .unwrap_err();
+    assert_eq!(error_key(error), "CUTE_FOX_TOKEN");
+}
+
+#[test]
+fn test_validation_errors() {
+    let invalid =
+        |data: &str| error_key(Config::from_toml_str(data).unwrap().validate().unwrap_err());
+
+    assert_eq!(invalid("requests_per_second = 0"), "requests_per_second");
+    assert_eq!(invalid("transaction_size = 0"), "transaction_size");
+    assert_eq!(invalid("api_version = \"\""), "api_version");
+    assert_eq!(invalid("tokens = [\"first\", \" \"]"), "tokens[1]");
+    assert_eq!(
+        invalid("[fields]\nbasic = [\"sex\", \"\"]"),
+        "fields.basic[1]"
+    );
+    assert_eq!(
+        invalid("[fields]\nbasic = [\"sex\", \"photo_max_orign\"]"),
+        "fields.basic[1]"
+    );
+
+    let config = Config::default();
+    assert_eq!(
+        error_key(config.fields("everything").unwrap_err()),
+        "fields.everything"
+    );
+    assert_eq!(error_key(config.tokens().unwrap_err()), "tokens");
+    assert_eq!(
+        error_key(CuteFox::from_config(&config).err().unwrap()),
+        "tokens"
+    );
+}
+
+#[test]
+fn test_default_fields() {
+    assert_eq!(Config::default().default_fields().unwrap(), None);
+
+    let config = Config::from_toml_str("[fields]\ndefault = [\"sex\", \"bdate\"]").unwrap();
+    assert_eq!(
+        config.default_fields().unwrap().unwrap().to_string(),
+        "sex,bdate"
+    );
+
+    let config = Config::from_toml_str("[fields]\ndefault = [\"sexx\"]").unwrap();
+    assert_eq!(
+        error_key(config.default_fields().unwrap_err()),
+        "fields.default[0]"
+    );
+}
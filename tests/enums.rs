@@ -0,0 +1,90 @@
+mod support;
+
+use cute_fox::{
+    stages::enums::{Attitude, OccupationType, Platform, Political, Relation, Sex},
+    CuteValue, SqliteStorage,
+};
+use rusqlite::{params, Connection, NO_PARAMS};
+use serde_json::json;
+
+#[test]
+fn test_codes() {
+    assert_eq!(Sex::from(2), Sex::Male);
+    assert_eq!(Relation::from(4), Relation::Married);
+    assert_eq!(Platform::from(7), Platform::Web);
+    assert_eq!(Political::from(9), Political::Libertarian);
+    assert_eq!(Attitude::Neutral.code(), 4);
+
+    assert_eq!(Relation::from(42), Relation::Unknown(42));
+    assert_eq!(Relation::Unknown(42).code(), 42);
+    assert_eq!(
+        OccupationType::from("army".to_string()),
+        OccupationType::Unknown("army".to_string())
+    );
+}
+
+#[test]
+fn test_serde() {
+    assert_eq!(
+        serde_json::from_value::<Sex>(json!(1)).unwrap(),
+        Sex::Female
+    );
+    assert_eq!(
+        serde_json::from_value::<Platform>(json!(99)).unwrap(),
+        Platform::Unknown(99)
+    );
+    assert_eq!(serde_json::to_value(Relation::InLove).unwrap(), json!(7));
+    assert_eq!(
+        serde_json::from_value::<OccupationType>(json!("university")).unwrap(),
+        OccupationType::University
+    );
+    assert_eq!(
+        serde_json::to_value(OccupationType::Work).unwrap(),
+        json!("work")
+    );
+
+    let user = support::fixture_users().remove(0);
+    let value = serde_json::to_value(&user).unwrap();
+    assert_eq!(value["sex"], json!(2));
+    assert_eq!(value["relation"], json!(0));
+    assert_eq!(value["last_seen"]["platform"], json!(7));
+    assert_eq!(value["occupation"]["type"], json!("university"));
+    assert_eq!(value["personal"]["political"], json!(4));
+}
+
+#[test]
+fn test_storage_keeps_codes() {
+    let mut conn = Connection::open_in_memory().unwrap();
+    CuteValue::Users(support::fixture_users())
+        .save(&mut conn, 10)
+        .unwrap();
+
+    let (sex, relation): (i64, i64) = conn
+        .query_row(
+            "SELECT sex, relation FROM objects WHERE id = 4",
+            NO_PARAMS,
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .unwrap();
+    assert_eq!((sex, relation), (1, 4));
+
+    let platform: Platform = conn
+        .query_row(
+            "SELECT platform FROM last_seen WHERE user_id = ?",
+            params![5],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(platform, Platform::Android);
+
+    let (kind, smoking): (String, Attitude) = conn
+        .query_row(
+            "SELECT occupation.type, personal.smoking FROM occupation
+             JOIN personal USING (user_id) WHERE user_id = 1",
+            NO_PARAMS,
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .unwrap();
+    assert_eq!(kind, "university");
+    assert_eq!(smoking, Attitude::VeryNegative);
+}
@@ -0,0 +1,56 @@
+use cute_fox::{
+    stages::fields::{FieldSet, UserField},
+    RobberError,
+};
+
+#[test]
+fn test_render() {
+    let fields = FieldSet::new()
+        .with(UserField::Sex)
+        .with(UserField::Bdate)
+        .with(UserField::Sex);
+
+    assert_eq!(fields.to_string(), "sex,bdate");
+    assert_eq!(FieldSet::new().to_string(), "");
+    assert_eq!(
+        FieldSet::contacts().to_string(),
+        "contacts,has_mobile,site,connections"
+    );
+    assert_eq!(FieldSet::all().iter().count(), UserField::ALL.len());
+
+    for name in FieldSet::PRESETS {
+        assert!(!FieldSet::preset(name).unwrap().is_empty(), "{}", name);
+    }
+    assert_eq!(FieldSet::preset("everything"), None);
+}
+
+#[test]
+fn test_parse() {
+    let fields: FieldSet = "sex, bdate,photo_max_orig".parse().unwrap();
+    assert!(fields.contains(UserField::PhotoMaxOrig));
+    assert_eq!(fields.to_string(), "sex,bdate,photo_max_orig");
+
+    for field in UserField::ALL {
+        assert_eq!(field.as_str().parse::<UserField>().unwrap(), *field);
+    }
+
+    match "sex, photo_max_orign".parse::<FieldSet>() {
+        Err(RobberError::FieldError { field }) => assert_eq!(field, "photo_max_orign"),
+        e => panic!("expected an unknown field error, got {:?}", e),
+    }
+}
+
+#[test]
+fn test_string_fallback() {
+    let fields = FieldSet::from("sex, photo_max_orign, sex");
+
+    assert!(fields.contains(UserField::Sex));
+    assert_eq!(fields.unknown(), &["photo_max_orign".to_string()]);
+    assert_eq!(fields.to_string(), "sex,photo_max_orign");
+
+    let mut education = FieldSet::education();
+    education.extend(fields);
+    assert!(education.contains(UserField::Universities));
+    assert!(education.contains(UserField::Sex));
+    assert_eq!(education.unknown().len(), 1);
+}
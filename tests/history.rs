@@ -0,0 +1,115 @@
+use cute_fox::{
+    history,
+    stages::{
+        fields::{FieldSet, UserField},
+        users::User,
+    },
+    CuteValue, SqliteStorage,
+};
+use rusqlite::Connection;
+
+fn store(conn: &mut Connection, user: User) {
+    CuteValue::Users(vec![user]).save(conn, 10).unwrap();
+}
+
+#[test]
+fn test_disabled_by_default() {
+    let mut conn = Connection::open_in_memory().unwrap();
+    store(&mut conn, User::builder(1, "A", "B").build());
+
+    assert!(!history::is_enabled(&conn).unwrap());
+    assert!(history::versions(&conn, 1).unwrap().is_empty());
+}
+
+#[test]
+fn test_versions_on_change() {
+    let mut conn = Connection::open_in_memory().unwrap();
+    history::enable(&mut conn).unwrap();
+    assert!(history::is_enabled(&conn).unwrap());
+
+    let user = |status: &str, followers: i64, at: i64| {
+        User::builder(1, "A", "B")
+            .status(status)
+            .followers_count(followers)
+            .fetched_at(at)
+            .build()
+    };
+    store(&mut conn, user("hello", 10, 100));
+    store(&mut conn, user("hello", 20, 200));
+    store(&mut conn, user("bye", 20, 300));
+    store(
+        &mut conn,
+        User::builder(2, "C", "D").fetched_at(300).build(),
+    );
+
+    let versions = history::versions(&conn, 1).unwrap();
+    assert_eq!(versions.len(), 2);
+    assert_eq!(
+        (versions[0].valid_from, versions[0].valid_to),
+        (100, Some(300))
+    );
+    assert_eq!(versions[0].user.status(), Some("hello"));
+    assert_eq!((versions[1].valid_from, versions[1].valid_to), (300, None));
+    assert_eq!(versions[1].user.status(), Some("bye"));
+
+    assert_eq!(
+        history::as_of(&conn, 1, 250)
+            .unwrap()
+            .unwrap()
+            .user
+            .status(),
+        Some("hello")
+    );
+    assert_eq!(
+        history::as_of(&conn, 1, 300)
+            .unwrap()
+            .unwrap()
+            .user
+            .status(),
+        Some("bye")
+    );
+    assert!(history::as_of(&conn, 1, 50).unwrap().is_none());
+    assert_eq!(history::versions(&conn, 2).unwrap().len(), 1);
+}
+
+#[test]
+fn test_refetch_with_fewer_fields() {
+    let mut conn = Connection::open_in_memory().unwrap();
+    history::enable(&mut conn).unwrap();
+
+    let fields = FieldSet::from(UserField::Status).with(UserField::Site);
+    store(
+        &mut conn,
+        User::builder(1, "A", "B")
+            .status("hello")
+            .site("example.com")
+            .fetched_at(100)
+            .fetched_fields(fields)
+            .build(),
+    );
+
+    // The site was not requested, so it is not considered removed.
+    store(
+        &mut conn,
+        User::builder(1, "A", "B")
+            .status("hello")
+            .fetched_at(200)
+            .fetched_fields(FieldSet::from(UserField::Status))
+            .build(),
+    );
+    assert_eq!(history::versions(&conn, 1).unwrap().len(), 1);
+
+    store(
+        &mut conn,
+        User::builder(1, "A", "B")
+            .status("bye")
+            .fetched_at(300)
+            .fetched_fields(FieldSet::from(UserField::Status))
+            .build(),
+    );
+
+    let versions = history::versions(&conn, 1).unwrap();
+    assert_eq!(versions.len(), 2);
+    assert_eq!(versions[1].user.status(), Some("bye"));
+    assert_eq!(versions[1].user.site(), Some("example.com"));
+}
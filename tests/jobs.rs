@@ -0,0 +1,171 @@
+mod support;
+
+use cute_fox::{
+    jobs, stages::fields::FieldSet, CuteExecutor, CuteFox, CuteTask, CuteValue, RobberError,
+    SqliteStorage,
+};
+use rusqlite::{Connection, NO_PARAMS};
+use support::{count_users, users_job, MockVk};
+
+#[tokio::test]
+async fn test_job_resumes_pending_chunks() {
+    let vk = MockVk::start().await;
+    let path = support::temp_database();
+    let fox = CuteFox::from_managers(vec![vk.manager("token")]).with_database(&path, 10);
+
+    let value = fox
+        .execute(users_job("all", (1..=2500).collect()))
+        .await
+        .unwrap();
+    let job = match value {
+        CuteValue::Job(e) => e,
+        e => panic!("expected a job, got {:?}", e),
+    };
+    assert_eq!((job.done, job.total), (3, 3));
+    assert!(job.finished_at.is_some());
+    assert_eq!(job.fields, FieldSet::basic());
+    assert_eq!(vk.calls("users.get"), 3);
+
+    let conn = Connection::open(&path).unwrap();
+    assert_eq!(count_users(&conn), 5);
+
+    // As if the run had stopped before the last chunk was stored.
+    conn.execute_batch(
+        "UPDATE job_chunks SET done_at = NULL WHERE chunk = 2;
+         UPDATE jobs SET finished_at = NULL;",
+    )
+    .unwrap();
+    let job = jobs::find(&conn, "all").unwrap().unwrap();
+    assert_eq!((job.done, job.finished_at), (2, None));
+
+    // The task is only used to create the job, the journal decides what is left.
+    fox.execute(users_job("all", vec![1])).await.unwrap();
+    assert_eq!(vk.calls("users.get"), 4);
+    assert!(jobs::find(&conn, "all")
+        .unwrap()
+        .unwrap()
+        .finished_at
+        .is_some());
+
+    fox.execute(users_job("all", Vec::new())).await.unwrap();
+    assert_eq!(vk.calls("users.get"), 4);
+
+    drop(conn);
+    std::fs::remove_file(path).unwrap();
+}
+
+#[tokio::test]
+async fn test_negative_ids_round_trip() {
+    let vk = MockVk::start().await;
+    let path = support::temp_database();
+    let fox = CuteFox::from_managers(vec![vk.manager("token")]).with_database(&path, 10);
+
+    let value = fox
+        .execute(users_job("negative", vec![-5, -4, -3, -1, 0, 1, 2, 7]))
+        .await
+        .unwrap();
+    assert!(matches!(value, CuteValue::Job(ref e) if e.finished_at.is_some()));
+
+    let conn = Connection::open(&path).unwrap();
+    let user_ids: String = conn
+        .query_row("SELECT user_ids FROM job_chunks", NO_PARAMS, |row| {
+            row.get(0)
+        })
+        .unwrap();
+    assert_eq!(user_ids, "-5--3,-1-2,7");
+    assert_eq!(count_users(&conn), 2);
+
+    drop(conn);
+    std::fs::remove_file(path).unwrap();
+}
+
+#[tokio::test]
+async fn test_job_records_retries() {
+    let vk = MockVk::start().await;
+    let path = support::temp_database();
+    let fox = CuteFox::from_managers(vec![vk.manager("token")]).with_database(&path, 10);
+
+    vk.fail_next("users.get", 6, 2);
+    let value = fox
+        .execute(users_job("retried", (1..=2500).collect()))
+        .await
+        .unwrap();
+    assert!(matches!(value, CuteValue::Job(ref e) if e.retries == 2));
+
+    // Retries of a later run add up.
+    let conn = Connection::open(&path).unwrap();
+    conn.execute_batch(
+        "UPDATE job_chunks SET done_at = NULL WHERE chunk = 0;
+         UPDATE jobs SET finished_at = NULL;",
+    )
+    .unwrap();
+    vk.fail_next("users.get", 6, 1);
+    fox.execute(users_job("retried", Vec::new())).await.unwrap();
+    assert_eq!(jobs::find(&conn, "retried").unwrap().unwrap().retries, 3);
+
+    drop(conn);
+    std::fs::remove_file(path).unwrap();
+}
+
+#[tokio::test]
+async fn test_failed_chunk_stays_pending() {
+    let vk = MockVk::start().await;
+    let path = support::temp_database();
+    let fox = CuteFox::from_managers(vec![vk.manager("token")]).with_database(&path, 10);
+
+    vk.fail_next("users.get", 100, 1);
+    assert!(fox
+        .execute(users_job("members", vec![1, 2, 3]))
+        .await
+        .is_err());
+
+    let conn = Connection::open(&path).unwrap();
+    let job = jobs::find(&conn, "members").unwrap().unwrap();
+    assert_eq!((job.done, job.total), (0, 1));
+    assert_eq!(count_users(&conn), 0);
+
+    let resume = CuteTask::Job {
+        name: "members".to_string(),
+        task: None,
+    };
+    fox.execute(resume).await.unwrap();
+    assert_eq!(count_users(&conn), 3);
+
+    jobs::remove(&conn, "members").unwrap();
+    assert!(jobs::find(&conn, "members").unwrap().is_none());
+
+    drop(conn);
+    std::fs::remove_file(path).unwrap();
+}
+
+#[tokio::test]
+async fn test_unknown_job() {
+    let vk = MockVk::start().await;
+    let path = support::temp_database();
+    let fox = CuteFox::from_managers(vec![vk.manager("token")]).with_database(&path, 10);
+
+    let error = fox
+        .execute(CuteTask::Job {
+            name: "missing".to_string(),
+            task: None,
+        })
+        .await
+        .unwrap_err();
+    assert!(matches!(error, RobberError::JobError { ref name, .. } if name == "missing"));
+
+    std::fs::remove_file(path).ok();
+}
+
+#[tokio::test]
+async fn test_save_job_value() {
+    let vk = MockVk::start().await;
+    let path = support::temp_database();
+    let fox = CuteFox::from_managers(vec![vk.manager("token")]).with_database(&path, 10);
+
+    let value = fox.execute(users_job("all", vec![1, 2])).await.unwrap();
+    let mut conn = Connection::open(&path).unwrap();
+    let error = value.save(&mut conn, 10).unwrap_err();
+    assert!(matches!(error, RobberError::JobError { ref name, .. } if name == "all"));
+
+    std::fs::remove_file(path).ok();
+}
@@ -0,0 +1,126 @@
+mod support;
+
+use cute_fox::{history, migrations, schema, CuteValue, RobberError, SqliteStorage};
+use rusqlite::{Connection, NO_PARAMS};
+
+fn column_names(conn: &Connection, table: &str) -> Vec<String> {
+    let mut statement = conn
+        .prepare(&format!("PRAGMA table_info(\"{}\")", table))
+        .unwrap();
+    let rows = statement
+        .query_map(NO_PARAMS, |row| row.get::<_, String>(1))
+        .unwrap();
+    rows.map(Result::unwrap).collect()
+}
+
+#[test]
+fn test_migrate_fresh_database() {
+    let mut conn = Connection::open_in_memory().unwrap();
+
+    assert_eq!(migrations::current_version(&conn).unwrap(), None);
+    assert_eq!(migrations::migrate(&mut conn).unwrap(), 0);
+    assert_eq!(
+        migrations::current_version(&conn).unwrap(),
+        Some(migrations::latest_version())
+    );
+
+    assert_eq!(
+        migrations::migrate(&mut conn).unwrap(),
+        migrations::latest_version()
+    );
+}
+
+#[test]
+fn test_migrate_old_database() {
+    let path = support::temp_copy("data/database.db");
+    let mut conn = Connection::open(&path).unwrap();
+    assert!(!column_names(&conn, "objects").contains(&"relation".to_string()));
+
+    assert_eq!(migrations::migrate(&mut conn).unwrap(), 0);
+    assert_eq!(
+        migrations::current_version(&conn).unwrap(),
+        Some(migrations::latest_version())
+    );
+    // Tables created by the earlier steps do not turn history on.
+    assert!(!history::is_enabled(&conn).unwrap());
+
+    let fresh = Connection::open_in_memory().unwrap();
+    schema::create(&fresh).unwrap();
+    for table in schema::TABLES {
+        let migrated = column_names(&conn, table);
+        for column in column_names(&fresh, table) {
+            assert!(migrated.contains(&column), "{}.{}", table, column);
+        }
+    }
+
+    drop(conn);
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_save_migrates_automatically() {
+    let path = support::temp_copy("data/database.db");
+    let mut conn = Connection::open(&path).unwrap();
+
+    CuteValue::Users(support::fixture_users())
+        .save(&mut conn, 100)
+        .unwrap();
+
+    let relation: Option<i64> = conn
+        .query_row(
+            "SELECT relation FROM objects WHERE id = 4",
+            NO_PARAMS,
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(relation, Some(4));
+
+    drop(conn);
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_newer_schema_is_refused() {
+    let mut conn = Connection::open_in_memory().unwrap();
+    migrations::migrate(&mut conn).unwrap();
+    conn.execute(
+        "INSERT INTO schema_version (version, description, applied_at) VALUES (?, 'future', 0)",
+        [migrations::latest_version() + 1],
+    )
+    .unwrap();
+
+    let error = migrations::migrate(&mut conn).unwrap_err();
+
+    assert!(matches!(error, RobberError::SchemaError { .. }));
+    assert!(error
+        .to_string()
+        .contains("newer than the supported version"));
+}
+
+/// Database at version 6, with `user_history` only if history was enabled back then.
+fn version_6_database(history: bool) -> Connection {
+    let mut conn = Connection::open_in_memory().unwrap();
+    migrations::migrate(&mut conn).unwrap();
+    conn.execute_batch("DROP TABLE settings; UPDATE schema_version SET version = 6;")
+        .unwrap();
+    if !history {
+        conn.execute_batch("DROP TABLE user_history").unwrap();
+    }
+    conn
+}
+
+#[test]
+fn test_migrate_keeps_enabled_history() {
+    // History used to be turned on by creating its table outside of the migrations.
+    let mut conn = version_6_database(true);
+    migrations::migrate(&mut conn).unwrap();
+    assert!(history::is_enabled(&conn).unwrap());
+
+    let mut conn = version_6_database(false);
+    migrations::migrate(&mut conn).unwrap();
+    assert!(!history::is_enabled(&conn).unwrap());
+    assert_eq!(
+        migrations::current_version(&conn).unwrap(),
+        Some(migrations::latest_version())
+    );
+}
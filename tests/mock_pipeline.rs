@@ -0,0 +1,245 @@
+mod support;
+
+use cute_fox::{
+    requests::errors::VkApiError,
+    stages::{fields::FieldSet, groups::GroupInteraction, users::UserInteraction},
+    CuteExecutor, CuteFox, CuteTask, CuteValue, RobberError, SqliteStorage,
+};
+use futures::TryStreamExt;
+use rusqlite::{Connection, NO_PARAMS};
+use support::MockVk;
+
+#[tokio::test]
+async fn test_get_members_ids_paginates() {
+    let vk = MockVk::start().await;
+    let api = vk.manager("token");
+
+    let ids = api.get_members_ids(2).await.unwrap();
+
+    assert_eq!(ids.len(), 2500);
+    assert_eq!(ids.first(), Some(&1));
+    assert_eq!(ids.last(), Some(&2500));
+    assert_eq!(vk.calls("groups.getMembers"), 3);
+}
+
+#[tokio::test]
+async fn test_get_users_partial() {
+    let vk = MockVk::start().await;
+    let api = vk.manager("token");
+
+    let users = api.get_users(&[1, 2, 99], "").await.unwrap();
+
+    assert_eq!(users.len(), 2);
+}
+
+#[tokio::test]
+async fn test_get_unknown_user() {
+    let vk = MockVk::start().await;
+    let api = vk.manager("token");
+
+    let result = api.get_user(99, "").await;
+
+    assert!(matches!(
+        result,
+        Err(RobberError::UserNotFound { user_id: 99 })
+    ));
+}
+
+#[tokio::test]
+async fn test_api_errors() {
+    let vk = MockVk::start().await;
+
+    let result = vk.manager("token").get_members_ids(999).await;
+    assert!(matches!(
+        result,
+        Err(RobberError::APIError(VkApiError::InvalidGroupId(_)))
+    ));
+
+    vk.revoke("revoked");
+    let result = vk.manager("revoked").get_user(1, "").await;
+    assert!(matches!(
+        result,
+        Err(RobberError::APIError(VkApiError::AuthFailed(_)))
+    ));
+}
+
+#[tokio::test]
+async fn test_rate_limit_errors_are_retried() {
+    let vk = MockVk::start().await;
+    let api = vk.manager("token");
+    vk.fail_next("users.get", 6, 2);
+
+    let users = api.get_users(&[1, 2], "").await.unwrap();
+
+    assert_eq!(users.len(), 2);
+    assert_eq!(api.stats().retries(), 2);
+    assert_eq!(vk.calls("users.get"), 3);
+}
+
+#[tokio::test]
+async fn test_execute_and_store() {
+    let vk = MockVk::start().await;
+    let fox = CuteFox::from_managers(vec![vk.manager("first"), vk.manager("second")]);
+
+    let value = fox
+        .execute(CuteTask::GetMembers {
+            group_id: 1,
+            fields: FieldSet::basic(),
+        })
+        .await
+        .unwrap();
+    let users = match &value {
+        CuteValue::Users(e) => e,
+        e => panic!("expected users, got {:?}", e),
+    };
+    assert_eq!(users.len(), 5);
+
+    let path = support::temp_database();
+    let mut conn = Connection::open(&path).unwrap();
+    value.save(&mut conn, 2).unwrap();
+
+    let count = |table: &str| -> i64 {
+        conn.query_row(
+            &format!("SELECT COUNT(*) FROM {}", table),
+            NO_PARAMS,
+            |row| row.get(0),
+        )
+        .unwrap()
+    };
+    assert_eq!(count("objects"), 5);
+    assert_eq!(count("career"), 1);
+    assert_eq!(count("relation_partner"), 2);
+    assert_eq!(count("universities"), 1);
+
+    drop(conn);
+    std::fs::remove_file(path).unwrap();
+}
+
+#[tokio::test]
+async fn test_execute_stream_yields_chunks() {
+    let vk = MockVk::start().await;
+    let fox = CuteFox::from_managers(vec![vk.manager("first"), vk.manager("second")]);
+
+    let mut chunks: Vec<usize> = fox
+        .execute_stream(CuteTask::GetMembers {
+            group_id: 2,
+            fields: FieldSet::basic(),
+        })
+        .map_ok(|value| match value {
+            CuteValue::Users(e) => e.len(),
+            e => panic!("expected users, got {:?}", e),
+        })
+        .try_collect()
+        .await
+        .unwrap();
+
+    // Chunks come as they finish, only users 1 to 5 exist.
+    chunks.sort_unstable();
+    assert_eq!(chunks, vec![0, 0, 5]);
+    assert_eq!(vk.calls("users.get"), 3);
+}
+
+#[tokio::test]
+async fn test_slow_consumer_holds_up_fetching() {
+    let vk = MockVk::start().await;
+    let fox = CuteFox::from_managers(vec![vk.manager("token")]);
+    let mut chunks = fox.execute_stream(CuteTask::GetUsers {
+        user_ids: (1..=20_000).collect(),
+        fields: FieldSet::basic(),
+    });
+
+    // Like a writer still busy with the first chunk.
+    chunks.try_next().await.unwrap().unwrap();
+    tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+
+    // One chunk waits to be taken and each of the four workers holds the one it fetched.
+    assert!(vk.calls("users.get") <= 6, "{}", vk.calls("users.get"));
+
+    let mut fetched = 1;
+    while chunks.try_next().await.unwrap().is_some() {
+        fetched += 1;
+    }
+    assert_eq!(fetched, 20);
+    assert_eq!(vk.calls("users.get"), 20);
+}
+
+#[tokio::test]
+async fn test_failed_chunks_move_to_other_tokens() {
+    let vk = MockVk::start().await;
+    vk.revoke("revoked");
+    let fox = CuteFox::from_managers(vec![vk.manager("revoked"), vk.manager("good")]);
+
+    let value = fox
+        .execute(CuteTask::GetUsers {
+            user_ids: (1..=2500).collect(),
+            fields: FieldSet::basic(),
+        })
+        .await
+        .unwrap();
+    match value {
+        CuteValue::Users(e) => assert_eq!(e.len(), 5),
+        e => panic!("expected users, got {:?}", e),
+    }
+
+    let stats: Vec<_> = fox.stats().collect();
+    assert_eq!(stats[0].users(), 0);
+    assert_eq!(stats[0].requeued(), stats[0].failures());
+    assert_eq!(stats[1].users(), 5);
+    assert_eq!(stats[1].calls(), 3);
+
+    let fox = CuteFox::from_managers(vec![vk.manager("revoked")]);
+    let result = fox
+        .execute(CuteTask::GetUsers {
+            user_ids: vec![1],
+            fields: FieldSet::basic(),
+        })
+        .await;
+    assert!(matches!(result, Err(RobberError::TokenError { .. })));
+    assert_eq!(fox.stats().next().unwrap().requeued(), 0);
+}
+
+#[tokio::test]
+async fn test_execute_keeps_input_order() {
+    let vk = MockVk::start().await;
+    // The first request is retried, so its chunk finishes after the others.
+    vk.fail_next("users.get", 6, 1);
+    let fox = CuteFox::from_managers(vec![vk.manager("first"), vk.manager("second")]);
+
+    // One known user at the start of each chunk, the other ids do not exist.
+    let user_ids = [5, 4, 3, 2, 1]
+        .iter()
+        .flat_map(|&id| std::iter::once(id).chain(10_000 * id..10_000 * id + 999))
+        .collect();
+    let value = fox
+        .execute(CuteTask::GetUsers {
+            user_ids,
+            fields: FieldSet::basic(),
+        })
+        .await
+        .unwrap();
+
+    let ids: Vec<i64> = match value {
+        CuteValue::Users(e) => e.iter().map(|e| e.id()).collect(),
+        e => panic!("expected users, got {:?}", e),
+    };
+    assert_eq!(ids, vec![5, 4, 3, 2, 1]);
+    assert_eq!(vk.calls("users.get"), 6);
+}
+
+#[tokio::test]
+async fn test_users_per_second_ignores_idle_time() {
+    let vk = MockVk::start().await;
+    let fox = CuteFox::from_managers(vec![vk.manager("token")]);
+    assert_eq!(fox.stats().next().unwrap().users_per_second(), 0.0);
+
+    tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+    fox.execute(CuteTask::GetUsers {
+        user_ids: vec![1, 2, 3, 4, 5],
+        fields: FieldSet::basic(),
+    })
+    .await
+    .unwrap();
+
+    // Counted from the construction of the manager it would be below 10 users per second.
+    assert!(fox.stats().next().unwrap().users_per_second() > 10.0);
+}
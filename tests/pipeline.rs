@@ -0,0 +1,167 @@
+mod support;
+
+use std::time::Duration;
+
+use cute_fox::{
+    stages::{fields::FieldSet, users::User},
+    CuteFox, CuteTask, RobberError, SaveMode, SaveReport,
+};
+use rusqlite::{Connection, NO_PARAMS};
+use support::{count_users, MockVk};
+
+#[tokio::test]
+async fn test_pipeline_stores_every_chunk() {
+    let vk = MockVk::start().await;
+    let path = support::temp_database();
+    let fox = CuteFox::from_managers(vec![vk.manager("first"), vk.manager("second")])
+        .with_database(&path, 2);
+
+    let report = fox
+        .run_pipeline(
+            CuteTask::GetMembers {
+                group_id: 2,
+                fields: FieldSet::basic(),
+            },
+            SaveMode::Abort,
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(
+        report,
+        SaveReport {
+            saved: 5,
+            failed: 0,
+            retries: 0
+        }
+    );
+    assert_eq!(vk.calls("users.get"), 3);
+    assert_eq!(count_users(&Connection::open(&path).unwrap()), 5);
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[tokio::test]
+async fn test_pipeline_reports_retries() {
+    let vk = MockVk::start().await;
+    let path = support::temp_database();
+    let fox = CuteFox::from_managers(vec![vk.manager("token")]).with_database(&path, 10);
+
+    vk.fail_next("users.get", 6, 2);
+    let report = fox
+        .run_pipeline(
+            CuteTask::GetUsers {
+                user_ids: vec![1, 2, 3],
+                fields: FieldSet::basic(),
+            },
+            SaveMode::Abort,
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(
+        report,
+        SaveReport {
+            saved: 3,
+            failed: 0,
+            retries: 2
+        }
+    );
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[tokio::test]
+async fn test_pipeline_refreshes_stale_users() {
+    let vk = MockVk::start().await;
+    let path = support::temp_database();
+    let fox = CuteFox::from_managers(vec![vk.manager("token")]).with_database(&path, 10);
+
+    let mut conn = Connection::open(&path).unwrap();
+    cute_fox::migrations::migrate(&mut conn).unwrap();
+    conn.execute(
+        "INSERT INTO objects (id, first_name, last_name, fetched_at) VALUES (3, 'Stale', 'User', 0)",
+        NO_PARAMS,
+    )
+    .unwrap();
+
+    let report = fox
+        .run_pipeline(
+            CuteTask::RefreshUsers {
+                older_than: Duration::from_secs(3600),
+                fields: FieldSet::basic(),
+            },
+            SaveMode::RecordErrors,
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(report.saved, 1);
+    assert_ne!(User::load(&conn, 3).unwrap().unwrap().first_name(), "Stale");
+    assert_eq!(count_users(&conn), 1);
+
+    drop(conn);
+    std::fs::remove_file(path).unwrap();
+}
+
+#[tokio::test]
+async fn test_pipeline_returns_fetch_errors() {
+    let vk = MockVk::start().await;
+    let path = support::temp_database();
+    let fox = CuteFox::from_managers(vec![vk.manager("token")]).with_database(&path, 10);
+    vk.fail_next("users.get", 100, 1);
+
+    let error = fox
+        .run_pipeline(
+            CuteTask::GetUsers {
+                user_ids: vec![1, 2],
+                fields: FieldSet::basic(),
+            },
+            SaveMode::Abort,
+        )
+        .await
+        .unwrap_err();
+
+    assert!(matches!(error, RobberError::APIError(_)));
+    assert_eq!(count_users(&Connection::open(&path).unwrap()), 0);
+
+    let fox = CuteFox::from_managers(vec![vk.manager("token")]);
+    let error = fox
+        .run_pipeline(
+            CuteTask::GetUsers {
+                user_ids: vec![1],
+                fields: FieldSet::basic(),
+            },
+            SaveMode::Abort,
+        )
+        .await
+        .unwrap_err();
+    assert!(matches!(error, RobberError::ConfigError { ref key, .. } if key == "database_path"));
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[tokio::test]
+async fn test_pipeline_refuses_jobs() {
+    let vk = MockVk::start().await;
+    let path = support::temp_database();
+    let fox = CuteFox::from_managers(vec![vk.manager("token")]).with_database(&path, 10);
+
+    let error = fox
+        .run_pipeline(
+            CuteTask::Job {
+                name: "all".to_string(),
+                task: Some(Box::new(CuteTask::GetUsers {
+                    user_ids: vec![1, 2],
+                    fields: FieldSet::basic(),
+                })),
+            },
+            SaveMode::Abort,
+        )
+        .await
+        .unwrap_err();
+    assert!(matches!(error, RobberError::JobError { ref name, .. } if name == "all"));
+    assert_eq!(vk.calls("users.get"), 0);
+
+    std::fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,84 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use cute_fox::{
+    requests::{api_manager::ApiManager, rate_limiter::RateLimiter},
+    RobberError,
+};
+
+#[tokio::test]
+async fn test_requests_are_spaced() {
+    let limiter = RateLimiter::new(20);
+    let started = Instant::now();
+
+    for _ in 0..5 {
+        limiter.acquire().await;
+    }
+
+    // The first request goes through at once, the other four wait 50 ms each.
+    assert!(started.elapsed() >= Duration::from_millis(190));
+}
+
+#[tokio::test]
+async fn test_limit_holds_across_tasks() {
+    let limiter = Arc::new(RateLimiter::new(20));
+    let started = Instant::now();
+
+    let tasks: Vec<_> = (0..6)
+        .map(|_| {
+            let limiter = limiter.clone();
+            tokio::spawn(async move { limiter.acquire().await })
+        })
+        .collect();
+    for task in tasks {
+        task.await.unwrap();
+    }
+
+    assert!(started.elapsed() >= Duration::from_millis(240));
+}
+
+#[test]
+fn test_limiter_is_shared_per_token() {
+    let first = RateLimiter::for_token("rate-limiter-test-token", 3).unwrap();
+    let second = RateLimiter::for_token("rate-limiter-test-token", 3).unwrap();
+    let other = RateLimiter::for_token("rate-limiter-other-token", 20).unwrap();
+
+    assert!(Arc::ptr_eq(&first, &second));
+    assert!(!Arc::ptr_eq(&first, &other));
+
+    // Existing managers keep the rate they were built with.
+    assert!(matches!(
+        RateLimiter::for_token("rate-limiter-test-token", 20),
+        Err(RobberError::ConfigError { ref key, .. }) if key == "requests_per_second"
+    ));
+    assert_eq!(first.requests_per_second(), 3);
+}
+
+#[test]
+fn test_default_manager_keeps_registered_rate() {
+    let limiter = RateLimiter::for_token("rate-limiter-configured-token", 20).unwrap();
+    let manager = ApiManager::builder("rate-limiter-configured-token")
+        .build()
+        .unwrap();
+
+    assert!(Arc::ptr_eq(manager.rate_limiter(), &limiter));
+    assert_eq!(limiter.requests_per_second(), 20);
+}
+
+#[tokio::test]
+async fn test_available_in_reserves_nothing() {
+    let limiter = RateLimiter::new(10);
+    assert_eq!(limiter.available_in(), Duration::from_secs(0));
+    assert_eq!(limiter.available_in(), Duration::from_secs(0));
+
+    limiter.acquire().await;
+    let wait = limiter.available_in();
+    assert!(wait > Duration::from_millis(50) && wait <= Duration::from_millis(100));
+
+    let started = Instant::now();
+    limiter.ready().await;
+    assert!(started.elapsed() >= Duration::from_millis(50));
+    assert_eq!(limiter.available_in(), Duration::from_secs(0));
+}
@@ -0,0 +1,109 @@
+mod support;
+
+use std::time::Duration;
+
+use cute_fox::{
+    stages::{fields::FieldSet, users::User},
+    CuteExecutor, CuteFox, CuteTask, CuteValue, RobberError, SqliteStorage,
+};
+use rusqlite::{Connection, NO_PARAMS};
+use support::MockVk;
+
+const DAY: Duration = Duration::from_secs(24 * 3600);
+
+#[test]
+fn test_store_records_fetched_at() {
+    let mut conn = Connection::open_in_memory().unwrap();
+    CuteValue::Users(vec![
+        User::builder(1, "A", "B").build(),
+        User::builder(2, "C", "D").fetched_at(100).build(),
+    ])
+    .save(&mut conn, 10)
+    .unwrap();
+
+    let first = User::load(&conn, 1).unwrap().unwrap();
+    assert!(first.fetched_at().unwrap() > 1_600_000_000);
+    assert_eq!(
+        User::load(&conn, 2).unwrap().unwrap().fetched_at(),
+        Some(100)
+    );
+
+    assert_eq!(User::stale_ids(&conn, DAY).unwrap(), vec![2]);
+    conn.execute(
+        "UPDATE objects SET fetched_at = NULL WHERE id = 1",
+        NO_PARAMS,
+    )
+    .unwrap();
+    assert_eq!(User::stale_ids(&conn, DAY).unwrap(), vec![1, 2]);
+}
+
+#[tokio::test]
+async fn test_refresh_only_stale_users() {
+    let vk = MockVk::start().await;
+    let path = support::temp_database();
+
+    let mut conn = Connection::open(&path).unwrap();
+    CuteValue::Users(support::fixture_users())
+        .save(&mut conn, 10)
+        .unwrap();
+    conn.execute_batch(
+        "UPDATE objects SET fetched_at = 0 WHERE id IN (2, 4);
+         UPDATE objects SET fetched_at = NULL, first_name = 'Stale' WHERE id = 5;",
+    )
+    .unwrap();
+    drop(conn);
+
+    let fox = CuteFox::from_managers(vec![vk.manager("token")]).with_database(&path, 10);
+    let value = fox
+        .execute(CuteTask::RefreshUsers {
+            older_than: DAY,
+            fields: FieldSet::basic(),
+        })
+        .await
+        .unwrap();
+
+    let users = match value {
+        CuteValue::Users(e) => e,
+        e => panic!("expected users, got {:?}", e),
+    };
+    assert_eq!(
+        users.iter().map(User::id).collect::<Vec<_>>(),
+        vec![2, 4, 5]
+    );
+    assert_eq!(vk.calls("users.get"), 1);
+
+    let conn = Connection::open(&path).unwrap();
+    assert!(User::stale_ids(&conn, DAY).unwrap().is_empty());
+    assert_ne!(User::load(&conn, 5).unwrap().unwrap().first_name(), "Stale");
+
+    let value = fox
+        .execute(CuteTask::RefreshUsers {
+            older_than: DAY,
+            fields: FieldSet::basic(),
+        })
+        .await
+        .unwrap();
+    let users = match value {
+        CuteValue::Users(e) => e,
+        e => panic!("expected users, got {:?}", e),
+    };
+    assert!(users.is_empty());
+
+    drop(conn);
+    std::fs::remove_file(path).unwrap();
+}
+
+#[tokio::test]
+async fn test_refresh_needs_database() {
+    let vk = MockVk::start().await;
+    let fox = CuteFox::from_managers(vec![vk.manager("token")]);
+
+    let error = fox
+        .execute(CuteTask::RefreshUsers {
+            older_than: DAY,
+            fields: FieldSet::new(),
+        })
+        .await
+        .unwrap_err();
+    assert!(matches!(error, RobberError::ConfigError { ref key, .. } if key == "database_path"));
+}
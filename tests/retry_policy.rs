@@ -0,0 +1,74 @@
+use std::time::Duration;
+
+use cute_fox::{
+    requests::{
+        errors::VkApiError,
+        retry::{ErrorClass, RetryPolicy},
+    },
+    RobberError,
+};
+
+fn api_error(code: i64) -> RobberError {
+    let data = format!(r#"{{ "error_code": {}, "error_msg": "" }}"#, code);
+    RobberError::APIError(serde_json::from_str::<VkApiError>(&data).unwrap())
+}
+
+#[test]
+fn test_error_classes() {
+    assert_eq!(
+        ErrorClass::of(&api_error(6)),
+        Some(ErrorClass::TooManyRequests)
+    );
+    assert_eq!(
+        ErrorClass::of(&api_error(9)),
+        Some(ErrorClass::FloodControl)
+    );
+    assert_eq!(ErrorClass::of(&api_error(10)), Some(ErrorClass::Server));
+    assert_eq!(ErrorClass::of(&api_error(5)), None);
+}
+
+#[test]
+fn test_retryable() {
+    let policy = RetryPolicy::default();
+    assert!(policy.is_retryable(&api_error(6)));
+    assert!(!policy.is_retryable(&api_error(9)));
+    assert!(!policy.is_retryable(&api_error(5)));
+
+    assert!(!RetryPolicy::none().is_retryable(&api_error(6)));
+}
+
+#[test]
+fn test_exponential_delay() {
+    let mut policy = RetryPolicy::default().with_jitter(0.0).unwrap();
+    policy.base_delay = Duration::from_millis(100);
+    policy.max_delay = Duration::from_secs(1);
+
+    assert_eq!(policy.delay(0), Duration::from_millis(100));
+    assert_eq!(policy.delay(1), Duration::from_millis(200));
+    assert_eq!(policy.delay(3), Duration::from_millis(800));
+    assert_eq!(policy.delay(4), Duration::from_secs(1));
+    assert_eq!(policy.delay(40), Duration::from_secs(1));
+}
+
+#[test]
+fn test_jitter_bounds() {
+    let mut policy = RetryPolicy::default().with_jitter(0.25).unwrap();
+    policy.base_delay = Duration::from_millis(1000);
+
+    for _ in 0..100 {
+        let delay = policy.delay(0);
+        assert!(delay >= Duration::from_millis(750));
+        assert!(delay <= Duration::from_millis(1250));
+    }
+}
+
+#[test]
+fn test_invalid_jitter_is_refused() {
+    for jitter in &[f64::NAN, -0.1, 1.5, f64::INFINITY] {
+        assert!(matches!(
+            RetryPolicy::default().with_jitter(*jitter),
+            Err(RobberError::ConfigError { ref key, .. }) if key == "jitter"
+        ));
+    }
+    assert_eq!(RetryPolicy::default().jitter(), 0.2);
+}
@@ -0,0 +1,74 @@
+mod support;
+
+use std::path::Path;
+
+use cute_fox::{migrations, schema, stages::users::User};
+use rusqlite::{Connection, NO_PARAMS};
+
+fn columns(conn: &Connection, table: &str) -> Vec<(String, String, bool, bool)> {
+    let mut statement = conn
+        .prepare(&format!("PRAGMA table_info(\"{}\")", table))
+        .unwrap();
+    let rows = statement
+        .query_map(NO_PARAMS, |row| {
+            Ok((
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, bool>(3)?,
+                row.get::<_, i64>(5)? > 0,
+            ))
+        })
+        .unwrap();
+    rows.map(Result::unwrap).collect()
+}
+
+#[test]
+fn test_schema_matches_migrated_clear_database() {
+    let conn = Connection::open_in_memory().unwrap();
+    schema::create(&conn).unwrap();
+
+    let path = support::temp_database();
+    let mut reference = Connection::open(&path).unwrap();
+    migrations::migrate(&mut reference).unwrap();
+
+    for table in schema::TABLES {
+        assert_eq!(
+            columns(&conn, table),
+            columns(&reference, table),
+            "{}",
+            table
+        );
+    }
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_create_is_idempotent() {
+    let conn = Connection::open_in_memory().unwrap();
+    schema::create(&conn).unwrap();
+    schema::create(&conn).unwrap();
+}
+
+#[test]
+fn test_store_into_fresh_database() {
+    let data = std::fs::read_to_string(
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/users.json"),
+    )
+    .unwrap();
+    let users: Vec<User> = serde_json::from_str(&data).unwrap();
+
+    let mut conn = Connection::open_in_memory().unwrap();
+    schema::create(&conn).unwrap();
+
+    let transaction = conn.transaction().unwrap();
+    for user in users {
+        user.store(&transaction, "objects").unwrap();
+    }
+    transaction.commit().unwrap();
+
+    let count: i64 = conn
+        .query_row("SELECT COUNT(*) FROM objects", NO_PARAMS, |row| row.get(0))
+        .unwrap();
+    assert_eq!(count, 5);
+}
@@ -0,0 +1,83 @@
+mod support;
+
+use cute_fox::{schema, CuteValue, RobberError, SaveMode, SaveReport, SqliteStorage};
+use rusqlite::{Connection, NO_PARAMS};
+use std::error::Error;
+
+/// Fresh database in which storing the career of user 1 fails.
+fn broken_database() -> Connection {
+    let conn = Connection::open_in_memory().unwrap();
+    schema::create(&conn).unwrap();
+    conn.execute_batch(
+        "CREATE TRIGGER reject_career BEFORE INSERT ON career
+         BEGIN SELECT RAISE(ABORT, 'career is read-only'); END",
+    )
+    .unwrap();
+    conn
+}
+
+fn count(conn: &Connection, query: &str) -> i64 {
+    conn.query_row(query, NO_PARAMS, |row| row.get(0)).unwrap()
+}
+
+#[test]
+fn test_abort_names_user_and_table() {
+    let mut conn = broken_database();
+
+    let result = CuteValue::Users(support::fixture_users()).save(&mut conn, 100);
+
+    match &result {
+        Err(RobberError::StoreError(e)) => {
+            assert_eq!(e.user_id, 1);
+            assert_eq!(e.table, "career");
+        }
+        e => panic!("unexpected result: {:?}", e),
+    }
+
+    let error = result.unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "failed to store user 1 into career: career is read-only"
+    );
+    // The SQLite failure is part of the message, so a chain printer shows it once.
+    let mut chain = error.to_string();
+    let mut source = error.source();
+    while let Some(e) = source {
+        chain.push_str(&e.to_string());
+        source = e.source();
+    }
+    assert_eq!(chain.matches("career is read-only").count(), 1);
+    assert_eq!(count(&conn, "SELECT COUNT(*) FROM objects"), 0);
+}
+
+#[test]
+fn test_record_errors_and_continue() {
+    let mut conn = broken_database();
+
+    let report = CuteValue::Users(support::fixture_users())
+        .save_with(&mut conn, 2, SaveMode::RecordErrors)
+        .unwrap();
+
+    assert_eq!(
+        report,
+        SaveReport {
+            saved: 4,
+            failed: 1,
+            retries: 0
+        }
+    );
+    assert_eq!(count(&conn, "SELECT COUNT(*) FROM objects"), 4);
+    assert_eq!(count(&conn, "SELECT COUNT(*) FROM objects WHERE id = 1"), 0);
+    assert_eq!(count(&conn, "SELECT COUNT(*) FROM universities"), 0);
+
+    let (user_id, table, payload): (i64, String, String) = conn
+        .query_row(
+            "SELECT user_id, \"table\", payload FROM store_errors",
+            NO_PARAMS,
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .unwrap();
+    assert_eq!(user_id, 1);
+    assert_eq!(table, "career");
+    assert!(payload.contains("\"Дуров\""));
+}
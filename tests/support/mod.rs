@@ -0,0 +1,276 @@
+//! In-process stand-in for the VK API, serving `users.get` and `groups.getMembers` from `tests/fixtures`.
+
+#![allow(dead_code)]
+
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    convert::Infallible,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use cute_fox::{
+    requests::{
+        api_manager::{ApiManager, ApiManagerBuilder},
+        rate_limiter::RateLimiter,
+        retry::RetryPolicy,
+    },
+    stages::{fields::FieldSet, users::User},
+    CuteTask,
+};
+use hyper::{
+    service::{make_service_fn, service_fn},
+    Body, Request, Response, Server,
+};
+use rusqlite::{Connection, NO_PARAMS};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+const PAGE_SIZE: usize = 1000;
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum GroupFixture {
+    Members { members: Vec<i64> },
+    Range { members_range: (i64, i64) },
+}
+
+#[derive(Default)]
+struct State {
+    users: HashMap<i64, Value>,
+    groups: HashMap<i64, Vec<i64>>,
+    failures: HashMap<String, VecDeque<i64>>,
+    revoked: HashSet<String>,
+    calls: HashMap<String, usize>,
+}
+
+pub struct MockVk {
+    addr: SocketAddr,
+    state: Arc<Mutex<State>>,
+}
+
+impl MockVk {
+    /// Starts the server on a free local port. Must be called inside a tokio runtime.
+    pub async fn start() -> Self {
+        let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+
+        let users: Vec<Value> = read_fixture(&fixtures.join("users.json"));
+        let groups: HashMap<String, GroupFixture> = read_fixture(&fixtures.join("groups.json"));
+
+        let state = State {
+            users: users
+                .into_iter()
+                .map(|user| (user["id"].as_i64().unwrap(), user))
+                .collect(),
+            groups: groups
+                .into_iter()
+                .map(|(id, group)| {
+                    let members = match group {
+                        GroupFixture::Members { members } => members,
+                        GroupFixture::Range {
+                            members_range: (from, to),
+                        } => (from..=to).collect(),
+                    };
+                    (id.parse().unwrap(), members)
+                })
+                .collect(),
+            ..Default::default()
+        };
+        let state = Arc::new(Mutex::new(state));
+
+        let service_state = state.clone();
+        let make_service = make_service_fn(move |_| {
+            let state = service_state.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request| {
+                    let response = handle(&state, request);
+                    async move { Ok::<_, Infallible>(response) }
+                }))
+            }
+        });
+
+        let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+        let addr = server.local_addr();
+        tokio::spawn(server);
+
+        Self { addr, state }
+    }
+
+    pub fn base_url(&self) -> String {
+        format!("http://{}/method", self.addr)
+    }
+
+    /// Builder pointed at this server, with a private fast limiter and short retry delays.
+    pub fn builder(&self, token: &str) -> ApiManagerBuilder {
+        ApiManager::builder(token)
+            .base_url(self.base_url())
+            .rate_limiter(Arc::new(RateLimiter::new(1000)))
+            .retry_policy({
+                let mut policy = RetryPolicy::default();
+                policy.base_delay = Duration::from_millis(1);
+                policy.max_delay = Duration::from_millis(10);
+                policy
+            })
+    }
+
+    pub fn manager(&self, token: &str) -> ApiManager {
+        self.builder(token).build().unwrap()
+    }
+
+    /// Makes the next `times` calls of `method` fail with VK error `error_code`.
+    pub fn fail_next(&self, method: &str, error_code: i64, times: usize) {
+        let mut state = self.state.lock().unwrap();
+        let failures = state.failures.entry(method.to_string()).or_default();
+        failures.extend(std::iter::repeat_n(error_code, times));
+    }
+
+    /// Makes every following call made with `token` fail with an authorization error.
+    pub fn revoke(&self, token: &str) {
+        self.state.lock().unwrap().revoked.insert(token.to_string());
+    }
+
+    /// Number of calls of `method` received so far, failed ones included.
+    pub fn calls(&self, method: &str) -> usize {
+        let state = self.state.lock().unwrap();
+        state.calls.get(method).copied().unwrap_or(0)
+    }
+}
+
+fn read_fixture<T: for<'de> Deserialize<'de>>(path: &Path) -> T {
+    let data = std::fs::read_to_string(path).unwrap();
+    serde_json::from_str(&data).unwrap()
+}
+
+/// Users of `tests/fixtures/users.json`, as `users.get` would return them.
+pub fn fixture_users() -> Vec<User> {
+    read_fixture(&Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/users.json"))
+}
+
+fn handle(state: &Mutex<State>, request: Request<Body>) -> Response<Body> {
+    let method = request
+        .uri()
+        .path()
+        .trim_start_matches("/method/")
+        .to_string();
+    let params: HashMap<String, String> =
+        form_urlencoded::parse(request.uri().query().unwrap_or("").as_bytes())
+            .into_owned()
+            .collect();
+
+    let mut state = state.lock().unwrap();
+    *state.calls.entry(method.clone()).or_default() += 1;
+
+    let token = params.get("access_token").cloned().unwrap_or_default();
+    let failure = state
+        .failures
+        .get_mut(&method)
+        .and_then(|failures| failures.pop_front());
+
+    let body = if state.revoked.contains(&token) {
+        error(
+            &method,
+            5,
+            "User authorization failed: invalid access_token (4).",
+        )
+    } else if let Some(code) = failure {
+        error(&method, code, "Injected failure")
+    } else {
+        match method.as_str() {
+            "users.get" => users_get(&state, &params),
+            "groups.getMembers" => groups_get_members(&state, &method, &params),
+            _ => error(&method, 3, "Unknown method passed"),
+        }
+    };
+
+    Response::builder()
+        .header("Content-Type", "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
+
+fn error(method: &str, code: i64, message: &str) -> Value {
+    json!({
+        "error": {
+            "error_code": code,
+            "error_msg": message,
+            "request_params": [{ "key": "method", "value": method }]
+        }
+    })
+}
+
+/// Known users are returned in the order asked for, unknown ids are silently dropped.
+fn users_get(state: &State, params: &HashMap<String, String>) -> Value {
+    let users: Vec<&Value> = params
+        .get("user_ids")
+        .map(String::as_str)
+        .unwrap_or("")
+        .split(',')
+        .filter_map(|id| id.trim().parse::<i64>().ok())
+        .filter_map(|id| state.users.get(&id))
+        .collect();
+
+    json!({ "response": users })
+}
+
+fn groups_get_members(state: &State, method: &str, params: &HashMap<String, String>) -> Value {
+    let group_id = params.get("group_id").and_then(|e| e.parse::<i64>().ok());
+    let members = match group_id.and_then(|id| state.groups.get(&id)) {
+        Some(members) => members,
+        None => return error(method, 125, "Invalid group id"),
+    };
+
+    let offset = params
+        .get("offset")
+        .and_then(|e| e.parse().ok())
+        .unwrap_or(0);
+    let count = params
+        .get("count")
+        .and_then(|e| e.parse().ok())
+        .unwrap_or(PAGE_SIZE);
+
+    let items: Vec<i64> = members.iter().skip(offset).take(count).copied().collect();
+
+    json!({ "response": { "count": members.len(), "items": items } })
+}
+
+/// Job `name` fetching the basic fields of `user_ids`.
+pub fn users_job(name: &str, user_ids: Vec<i32>) -> CuteTask {
+    CuteTask::Job {
+        name: name.to_string(),
+        task: Some(Box::new(CuteTask::GetUsers {
+            user_ids,
+            fields: FieldSet::basic(),
+        })),
+    }
+}
+
+/// Users stored in `objects`.
+pub fn count_users(conn: &Connection) -> i64 {
+    conn.query_row("SELECT COUNT(*) FROM objects", NO_PARAMS, |row| row.get(0))
+        .unwrap()
+}
+
+/// Fresh copy of `data/clear_database.db` in the temporary directory.
+pub fn temp_database() -> PathBuf {
+    temp_copy("data/clear_database.db")
+}
+
+/// Copy of a file of the repository in the temporary directory.
+pub fn temp_copy(name: &str) -> PathBuf {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    let path = std::env::temp_dir().join(format!(
+        "cute_fox_{}_{}.db",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    let source = Path::new(env!("CARGO_MANIFEST_DIR")).join(name);
+    std::fs::copy(source, &path).unwrap();
+
+    path
+}
@@ -0,0 +1,173 @@
+mod support;
+
+use std::time::{Duration, Instant};
+
+use cute_fox::{
+    requests::{
+        errors::{ErrorObject, VkApiError},
+        health::{TokenHealth, DAILY_LIMIT_COOLDOWN},
+    },
+    stages::fields::FieldSet,
+    CuteExecutor, CuteFox, CuteTask, CuteValue, RobberError,
+};
+use support::MockVk;
+
+fn api_error(error_code: i64) -> RobberError {
+    RobberError::APIError(VkApiError::from(ErrorObject {
+        error_code,
+        error_msg: "failure".to_string(),
+        request_params: Vec::new(),
+        captcha_sid: None,
+        captcha_img: None,
+    }))
+}
+
+fn users(value: CuteValue) -> usize {
+    match value {
+        CuteValue::Users(e) => e.len(),
+        e => panic!("expected users, got {:?}", e),
+    }
+}
+
+#[test]
+fn test_health_after_errors() {
+    assert!(matches!(
+        TokenHealth::after(&api_error(5)),
+        Some(TokenHealth::Invalid { .. })
+    ));
+    assert!(matches!(
+        TokenHealth::after(&api_error(2)),
+        Some(TokenHealth::Invalid { .. })
+    ));
+
+    match TokenHealth::after(&api_error(29)) {
+        Some(TokenHealth::RateLimited { until, reason }) => {
+            assert!(until > Instant::now() + DAILY_LIMIT_COOLDOWN - Duration::from_secs(5));
+            assert_eq!(reason, "failure (code 29)");
+        }
+        e => panic!("expected a rate limit, got {:?}", e),
+    }
+    for code in &[6, 9, 14] {
+        assert!(matches!(
+            TokenHealth::after(&api_error(*code)),
+            Some(TokenHealth::RateLimited { .. })
+        ));
+    }
+
+    assert_eq!(TokenHealth::after(&api_error(18)), None);
+    assert_eq!(TokenHealth::after(&api_error(100)), None);
+}
+
+#[tokio::test]
+async fn test_invalid_token_is_quarantined() {
+    let vk = MockVk::start().await;
+    vk.revoke("revoked");
+    let fox = CuteFox::from_managers(vec![vk.manager("revoked"), vk.manager("good")]);
+
+    let value = fox
+        .execute(CuteTask::GetMembers {
+            group_id: 2,
+            fields: FieldSet::basic(),
+        })
+        .await
+        .unwrap();
+    assert_eq!(users(value), 5);
+
+    let health: Vec<_> = fox.health().collect();
+    assert!(matches!(health[0], TokenHealth::Invalid { ref reason } if reason.contains("code 5")));
+    assert_eq!(health[1], TokenHealth::Healthy);
+
+    // Only the members call fails on the revoked token, it gets no chunks afterwards.
+    let calls = fox.stats().next().unwrap().calls();
+    fox.execute(CuteTask::GetUsers {
+        user_ids: (1..=5000).collect(),
+        fields: FieldSet::basic(),
+    })
+    .await
+    .unwrap();
+    assert_eq!(fox.stats().next().unwrap().calls(), calls);
+}
+
+#[tokio::test]
+async fn test_every_token_invalid() {
+    let vk = MockVk::start().await;
+    vk.revoke("revoked");
+    let fox = CuteFox::from_managers(vec![vk.manager("revoked")]);
+
+    let error = fox
+        .execute(CuteTask::GetUsers {
+            user_ids: (1..=5000).collect(),
+            fields: FieldSet::basic(),
+        })
+        .await
+        .unwrap_err();
+    assert!(matches!(error, RobberError::TokenError { .. }));
+
+    let error = fox
+        .execute(CuteTask::GetMembers {
+            group_id: 1,
+            fields: FieldSet::basic(),
+        })
+        .await
+        .unwrap_err();
+    assert!(matches!(error, RobberError::TokenError { .. }));
+}
+
+#[tokio::test]
+async fn test_rate_limited_token_rests() {
+    let vk = MockVk::start().await;
+    let fox = CuteFox::from_managers(vec![vk.manager("first"), vk.manager("second")]);
+    vk.fail_next("users.get", 9, 1);
+
+    let value = fox
+        .execute(CuteTask::GetUsers {
+            user_ids: (1..=3000).collect(),
+            fields: FieldSet::basic(),
+        })
+        .await
+        .unwrap();
+    assert_eq!(users(value), 5);
+
+    let limited = fox
+        .health()
+        .filter(|e| matches!(e, TokenHealth::RateLimited { .. }))
+        .count();
+    assert_eq!(limited, 1);
+    assert_eq!(fox.stats().map(|e| e.requeued()).sum::<u64>(), 1);
+    assert_eq!(vk.calls("users.get"), 4);
+}
+
+#[tokio::test]
+async fn test_daily_limit_is_not_waited_for() {
+    let vk = MockVk::start().await;
+    vk.fail_next("users.get", 29, 1);
+    let fox = CuteFox::from_managers(vec![vk.manager("first"), vk.manager("second")]);
+
+    // The other token takes the chunks of the one that reached its quota.
+    let value = tokio::time::timeout(
+        Duration::from_secs(5),
+        fox.execute(CuteTask::GetUsers {
+            user_ids: (1..=3000).collect(),
+            fields: FieldSet::basic(),
+        }),
+    )
+    .await
+    .unwrap()
+    .unwrap();
+    assert_eq!(users(value), 5);
+
+    // The only token left rests for longer than `MAX_RATE_LIMIT_WAIT`.
+    let fox = CuteFox::from_managers(vec![vk.manager("third")]);
+    vk.fail_next("users.get", 29, 1);
+    let error = tokio::time::timeout(
+        Duration::from_secs(5),
+        fox.execute(CuteTask::GetUsers {
+            user_ids: (1..=3000).collect(),
+            fields: FieldSet::basic(),
+        }),
+    )
+    .await
+    .unwrap()
+    .unwrap_err();
+    assert!(matches!(error, RobberError::TokenError { .. }));
+}
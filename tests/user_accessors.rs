@@ -0,0 +1,109 @@
+mod support;
+
+use cute_fox::{
+    stages::{
+        birth_date::BirthDate,
+        enums::{Attitude, OccupationType, Platform, Political, Relation, Sex},
+        users::{CareerInfo, City, LastSeen, Occupation, University, User},
+    },
+    CuteValue, SqliteStorage,
+};
+use rusqlite::{Connection, NO_PARAMS};
+
+#[test]
+fn test_accessors() {
+    let user = support::fixture_users().remove(0);
+
+    assert_eq!(user.id(), 1);
+    assert_eq!(user.first_name(), "Павел");
+    assert_eq!(user.screen_name(), Some("durov"));
+    assert_eq!(user.status(), None);
+    assert_eq!(user.sex(), Some(Sex::Male));
+    assert_eq!(user.relation(), Some(Relation::NotSpecified));
+    assert_eq!(
+        user.bdate(),
+        Some(BirthDate::new(10, 10, Some(1984)).unwrap())
+    );
+    assert_eq!(user.city().map(|e| e.id()), Some(2));
+    assert_eq!(user.counters().map(|e| e.photos()), Some(297));
+
+    let last_seen = user.last_seen().unwrap();
+    assert_eq!(last_seen.platform(), Some(Platform::Web));
+    assert_eq!(last_seen.time(), 1619955329);
+
+    let occupation = user.occupation().unwrap();
+    assert_eq!(occupation.kind(), &OccupationType::University);
+    assert_eq!(occupation.name(), Some("СПбГУ"));
+
+    let personal = user.personal().unwrap();
+    assert_eq!(personal.political(), Some(Political::Liberal));
+    assert_eq!(personal.langs(), &["Русский", "English"]);
+    assert_eq!(personal.smoking(), Some(Attitude::VeryNegative));
+    assert_eq!(personal.religion(), None);
+
+    assert_eq!(user.career().len(), 1);
+    assert_eq!(user.career()[0].position(), Some("Founder"));
+    assert_eq!(user.career()[0].company(), None);
+    assert!(user.military().is_empty());
+    assert_eq!(user.universities()[0].graduation(), Some(2006));
+    assert_eq!(user.schools()[0].kind(), Some(2));
+    assert_eq!(user.schools()[0].id(), Some("1035"));
+    assert_eq!(user.relatives()[0].kind(), "sibling");
+}
+
+#[test]
+fn test_clone_and_eq() {
+    let users = support::fixture_users();
+    let copy = users.clone();
+
+    assert_eq!(users, copy);
+    assert_ne!(users[0], users[1]);
+}
+
+#[test]
+fn test_builder() {
+    let user = User::builder(42, "Лисид", "Лаконский")
+        .sex(Sex::Male)
+        .bdate("26.7.2004".parse::<BirthDate>().unwrap())
+        .screen_name("scoped_lock")
+        .relation(Relation::Single)
+        .followers_count(10)
+        .city(City::new(2))
+        .last_seen(LastSeen::new(1619955329).with_platform(Platform::Web))
+        .career(vec![CareerInfo::default()
+            .with_company("Cute Fox")
+            .with_position("Founder")
+            .with_from(2021)])
+        .occupation(
+            Occupation::new(OccupationType::University)
+                .with_id(1)
+                .with_name("СПбГУ"),
+        )
+        .universities(vec![University::new(1, 1, 2, "СПбГУ").with_graduation(2026)])
+        .build();
+
+    assert_eq!(user.id(), 42);
+    assert_eq!(user.last_name(), "Лаконский");
+    assert_eq!(user.screen_name(), Some("scoped_lock"));
+    assert_eq!(user.bdate().and_then(|e| e.year()), Some(2004));
+    assert_eq!(user.city().map(|e| e.id()), Some(2));
+    assert_eq!(user.last_seen().unwrap().platform(), Some(Platform::Web));
+    assert_eq!(user.career()[0].position(), Some("Founder"));
+    assert_eq!(user.career()[0].until(), None);
+    assert_eq!(user.occupation().unwrap().name(), Some("СПбГУ"));
+    assert_eq!(user.universities()[0].graduation(), Some(2026));
+    assert_eq!(user.nickname(), None);
+    assert!(user.personal().is_none());
+
+    let mut conn = Connection::open_in_memory().unwrap();
+    CuteValue::Users(vec![user]).save(&mut conn, 10).unwrap();
+
+    let (sex, screen_name): (i64, String) = conn
+        .query_row(
+            "SELECT sex, screen_name FROM objects WHERE id = 42",
+            NO_PARAMS,
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .unwrap();
+    assert_eq!((sex, screen_name.as_str()), (2, "scoped_lock"));
+}
@@ -0,0 +1,155 @@
+mod support;
+
+use cute_fox::{
+    stages::{
+        fields::{FieldSet, UserField},
+        users::User,
+    },
+    CuteValue, SqliteStorage,
+};
+use rusqlite::{params, Connection};
+use serde_json::Value;
+
+/// JSON of `user` without the keys `load` cannot tell apart from missing ones.
+fn comparable(user: &User) -> Value {
+    fn strip(value: Value) -> Value {
+        match value {
+            Value::Object(map) => Value::Object(
+                map.into_iter()
+                    .map(|(k, v)| (k, strip(v)))
+                    .filter(|(_, v)| !v.is_null() && v != &Value::Array(vec![]))
+                    .collect(),
+            ),
+            Value::Array(values) => Value::Array(values.into_iter().map(strip).collect()),
+            value => value,
+        }
+    }
+    strip(serde_json::to_value(user).unwrap())
+}
+
+fn stored_fixture() -> Connection {
+    let mut conn = Connection::open_in_memory().unwrap();
+    CuteValue::Users(support::fixture_users())
+        .save(&mut conn, 10)
+        .unwrap();
+    conn
+}
+
+#[test]
+fn test_round_trip() {
+    let conn = stored_fixture();
+
+    for user in support::fixture_users() {
+        let loaded = User::load(&conn, user.id()).unwrap().unwrap();
+        assert_eq!(comparable(&loaded), comparable(&user), "{}", user.id());
+    }
+
+    let durov = User::load(&conn, 1).unwrap().unwrap();
+    assert_eq!(durov.career()[0].position(), Some("Founder"));
+    assert_eq!(durov.schools()[0].id(), Some("1035"));
+    assert_eq!(durov.relatives()[0].kind(), "sibling");
+    assert_eq!(durov.personal().unwrap().langs(), &["Русский", "English"]);
+    assert_eq!(durov.verified(), Some(1));
+
+    assert_eq!(User::load(&conn, 1000).unwrap(), None);
+}
+
+#[test]
+fn test_load_many_and_all() {
+    let conn = stored_fixture();
+
+    let users = User::load_many(&conn, &[3, 1000, 1]).unwrap();
+    assert_eq!(users.iter().map(User::id).collect::<Vec<_>>(), vec![3, 1]);
+
+    let all = User::load_all(&conn)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(
+        all.iter().map(User::id).collect::<Vec<_>>(),
+        vec![1, 2, 3, 4, 5]
+    );
+}
+
+#[test]
+fn test_load_all_in_batches() {
+    let mut conn = Connection::open_in_memory().unwrap();
+    let users: Vec<User> = (1..=250)
+        .map(|id| User::builder(id, "First", "Last").build())
+        .collect();
+    CuteValue::Users(users).save(&mut conn, 100).unwrap();
+
+    let ids: Vec<i64> = User::load_all(&conn).map(|e| e.unwrap().id()).collect();
+    assert_eq!(ids, (1..=250).collect::<Vec<_>>());
+}
+
+#[test]
+fn test_store_replaces_nested_rows() {
+    let mut conn = stored_fixture();
+    CuteValue::Users(support::fixture_users())
+        .save(&mut conn, 10)
+        .unwrap();
+
+    let careers: i64 = conn
+        .query_row(
+            "SELECT COUNT(*) FROM career WHERE user_id = ?",
+            params![1],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(careers, 1);
+
+    // Nested values the user was not fetched with are kept.
+    let user = User::load(&conn, 1).unwrap().unwrap();
+    let tx = conn.transaction().unwrap();
+    User::builder(1, user.first_name(), user.last_name())
+        .build()
+        .store(&tx, "objects")
+        .unwrap();
+    tx.commit().unwrap();
+
+    let user = User::load(&conn, 1).unwrap().unwrap();
+    assert_eq!(user.career().len(), 1);
+    assert!(user.occupation().is_some());
+
+    // Fetched without a value, they are removed.
+    let tx = conn.transaction().unwrap();
+    User::builder(1, user.first_name(), user.last_name())
+        .fetched_fields(FieldSet::from(UserField::Career))
+        .build()
+        .store(&tx, "objects")
+        .unwrap();
+    tx.commit().unwrap();
+
+    let user = User::load(&conn, 1).unwrap().unwrap();
+    assert!(user.career().is_empty());
+    assert!(user.occupation().is_some());
+}
+
+#[test]
+fn test_refetch_keeps_columns_not_fetched() {
+    let mut conn = stored_fixture();
+
+    let tx = conn.transaction().unwrap();
+    User::builder(1, "Pavel", "Durov")
+        .status("Away")
+        .fetched_fields(FieldSet::from(UserField::Status))
+        .build()
+        .store(&tx, "objects")
+        .unwrap();
+    tx.commit().unwrap();
+
+    let user = User::load(&conn, 1).unwrap().unwrap();
+    assert_eq!(user.status(), Some("Away"));
+    assert_eq!(user.site(), Some("http://t.me/durov"));
+    assert_eq!(user.verified(), Some(1));
+}
+
+#[test]
+fn test_load_invalid_stored_bdate() {
+    let conn = stored_fixture();
+    conn.execute_batch("UPDATE objects SET bdate = '31.2.1990' WHERE id = 1")
+        .unwrap();
+
+    assert_eq!(User::load(&conn, 1).unwrap().unwrap().bdate(), None);
+    assert!(User::load_all(&conn).all(|e| e.is_ok()));
+}